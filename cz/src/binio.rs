@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 pub struct BitIo {
     data: Vec<u8>,
     byte_offset: usize,
@@ -1,11 +1,19 @@
+use alloc::{string::String, vec, vec::Vec};
+use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "imagequant")]
 use imagequant::Attributes;
 use rgb::{ComponentSlice, RGBA8};
+#[cfg(feature = "std")]
 use std::{
-    collections::HashMap,
-    io::{Read, Seek},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter},
+    path::Path,
 };
 
-use crate::common::{CommonHeader, CzError};
+use crate::{
+    common::{CommonHeader, CzError},
+    io::{Read, Seek, Write},
+};
 
 /// A palette of RGBA values for indexed color
 #[derive(Debug, Clone)]
@@ -14,6 +22,11 @@ pub struct Palette {
 }
 
 impl Palette {
+    /// Create a palette from a list of colors
+    pub fn new(colors: Vec<RGBA8>) -> Self {
+        Self { colors }
+    }
+
     /// Get the list of colors from the palette
     pub fn colors(&self) -> &Vec<RGBA8> {
         &self.colors
@@ -28,9 +41,454 @@ impl Palette {
         self.colors.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn get(&self, index: usize) -> Option<&RGBA8> {
         self.colors.get(index)
     }
+
+    /// Check whether the palette is a full grayscale or alpha ramp, where
+    /// each index maps directly to a single channel value.
+    pub fn grayscale_ramp(&self) -> Option<GrayscaleRamp> {
+        if self.colors.len() != 256 {
+            return None;
+        }
+
+        [GrayscaleRamp::Alpha, GrayscaleRamp::Luma]
+            .into_iter()
+            .find(|ramp| {
+                self.colors
+                    .iter()
+                    .enumerate()
+                    .all(|(i, c)| *c == ramp.color(i as u8))
+            })
+    }
+
+    /// Check whether every pixel of an RGBA bitmap has an exact match in the
+    /// palette, so it can be indexed without quantizing.
+    pub fn covers(&self, rgba: &[u8]) -> bool {
+        let colors: HashSet<&[u8]> = self.colors.iter().map(|c| c.as_slice()).collect();
+
+        rgba.chunks_exact(4).all(|c| colors.contains(c))
+    }
+
+    /// Create a palette of exactly the colors used in an RGBA bitmap, in the
+    /// order they first appear. Returns [`None`] if there are more than 256.
+    pub fn exact(rgba: &[u8]) -> Option<Self> {
+        let mut seen = HashSet::new();
+        let mut colors = Vec::new();
+
+        for c in rgba.chunks_exact(4) {
+            if seen.insert(c) {
+                if colors.len() == 256 {
+                    return None;
+                }
+                colors.push(RGBA8::new(c[0], c[1], c[2], c[3]));
+            }
+        }
+
+        Some(Self { colors })
+    }
+}
+
+/// Choose the smallest bit depth which stores a bitmap of RGBA pixels without
+/// any loss: 24 bits if it is fully opaque, 8 bits if it has 256 or fewer
+/// colors, and 32 bits otherwise.
+pub fn choose_depth(rgba: &[u8]) -> u16 {
+    if rgba.chunks_exact(4).all(|p| p[3] == 0xFF) {
+        24
+    } else if Palette::exact(rgba).is_some() {
+        8
+    } else {
+        32
+    }
+}
+
+/// Split an RGBA bitmap into an RGB bitmap and a separate alpha plane, the
+/// way CZ4 files store them.
+pub fn split_alpha(rgba: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut rgb = Vec::with_capacity(rgba.len() / 4 * 3);
+    let mut alpha = Vec::with_capacity(rgba.len() / 4);
+
+    for pixel in rgba.chunks_exact(4) {
+        rgb.extend_from_slice(&pixel[0..3]);
+        alpha.push(pixel[3]);
+    }
+
+    (rgb, alpha)
+}
+
+/// Join an RGB bitmap and an alpha plane of the same size into an RGBA
+/// bitmap. This is the opposite of [`split_alpha()`].
+pub fn join_alpha(rgb: &[u8], alpha: &[u8]) -> Result<Vec<u8>, CzError> {
+    if rgb.len() != alpha.len() * 3 {
+        return Err(CzError::BitmapFormat);
+    }
+
+    Ok(rgb
+        .chunks_exact(3)
+        .zip(alpha)
+        .flat_map(|(c, a)| [c[0], c[1], c[2], *a])
+        .collect())
+}
+
+/// Number of colors per row in a palette swatch image
+pub const SWATCH_WIDTH: usize = 16;
+
+/// Keyword of the PNG text chunk holding the number of colors in a swatch
+#[cfg(feature = "png")]
+const SWATCH_LEN_KEY: &str = "Palette Colors";
+
+/// The palette file formats which [`Palette::load()`] and [`Palette::save()`]
+/// support
+#[cfg(feature = "std")]
+enum PaletteFormat {
+    Gpl,
+    Act,
+    #[cfg(feature = "png")]
+    Png,
+}
+
+#[cfg(feature = "std")]
+impl PaletteFormat {
+    /// Find the format of a palette file from its extension
+    fn from_path(path: &Path) -> Result<Self, CzError> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "gpl" => Ok(Self::Gpl),
+            "act" => Ok(Self::Act),
+            #[cfg(feature = "png")]
+            "png" => Ok(Self::Png),
+            _ => Err(CzError::UnknownPaletteType(extension)),
+        }
+    }
+}
+
+impl Palette {
+    /// Load a palette from a GIMP (`.gpl`), Adobe Color Table (`.act`) or,
+    /// with the `png` feature, PNG swatch (`.png`) file, determined by the
+    /// file extension
+    #[cfg(feature = "std")]
+    pub fn load<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, CzError> {
+        let format = PaletteFormat::from_path(path.as_ref())?;
+
+        let mut input = BufReader::new(File::open(path)?);
+        match format {
+            PaletteFormat::Gpl => Self::from_gpl(&mut input),
+            PaletteFormat::Act => Self::from_act(&mut input),
+            #[cfg(feature = "png")]
+            PaletteFormat::Png => Self::from_png(input),
+        }
+    }
+
+    /// Save a palette as a GIMP (`.gpl`), Adobe Color Table (`.act`) or,
+    /// with the `png` feature, PNG swatch (`.png`) file, determined by the
+    /// file extension
+    #[cfg(feature = "std")]
+    pub fn save<P: ?Sized + AsRef<Path>>(&self, path: &P) -> Result<(), CzError> {
+        let format = PaletteFormat::from_path(path.as_ref())?;
+
+        let name = path
+            .as_ref()
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut output = BufWriter::new(File::create(path)?);
+        match format {
+            PaletteFormat::Gpl => self.write_gpl(&mut output, &name)?,
+            PaletteFormat::Act => self.write_act(&mut output)?,
+            #[cfg(feature = "png")]
+            PaletteFormat::Png => self.write_png(&mut output)?,
+        }
+        output.flush()?;
+
+        Ok(())
+    }
+
+    /// Read a palette from a GIMP palette, with either RGB or RGBA channels
+    #[cfg(feature = "std")]
+    pub fn from_gpl<T: BufRead>(input: &mut T) -> Result<Self, CzError> {
+        let mut lines = input.lines();
+
+        match lines.next() {
+            Some(Ok(magic)) if magic.trim() == "GIMP Palette" => (),
+            _ => return Err(CzError::Corrupt(String::from("Not a GIMP palette"))),
+        }
+
+        let mut channels = 3;
+        let mut colors = Vec::new();
+        for line in lines {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            } else if let Some((key, value)) = line.split_once(':') {
+                if key.trim() == "Channels" && value.trim() == "RGBA" {
+                    channels = 4;
+                }
+                continue;
+            }
+
+            let mut rgba = [0xFF; 4];
+            let mut values = line.split_whitespace();
+            for channel in rgba.iter_mut().take(channels) {
+                *channel = values
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or(CzError::Corrupt(format!("Invalid palette color: {line}")))?;
+            }
+
+            colors.push(RGBA8::from(rgba));
+        }
+
+        if colors.len() > 256 {
+            return Err(CzError::PaletteError);
+        }
+
+        Ok(Self { colors })
+    }
+
+    /// Write the palette as a GIMP palette with RGBA channels
+    #[cfg(feature = "std")]
+    pub fn write_gpl<T: Write>(&self, output: &mut T, name: &str) -> Result<(), CzError> {
+        writeln!(output, "GIMP Palette")?;
+        writeln!(output, "Name: {name}")?;
+        writeln!(output, "Columns: {SWATCH_WIDTH}")?;
+        writeln!(output, "Channels: RGBA")?;
+        writeln!(output, "#")?;
+
+        for (i, c) in self.colors.iter().enumerate() {
+            writeln!(
+                output,
+                "{:>3} {:>3} {:>3} {:>3}\tIndex {i}",
+                c.r, c.g, c.b, c.a
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a palette from an Adobe Color Table. These have no alpha channel,
+    /// so all colors are opaque except for the transparent index if present.
+    pub fn from_act<T: Read>(input: &mut T) -> Result<Self, CzError> {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+
+        if data.len() != 768 && data.len() != 772 {
+            return Err(CzError::Corrupt(String::from("Invalid color table length")));
+        }
+
+        let (count, transparent) = if data.len() == 772 {
+            (
+                u16::from_be_bytes([data[768], data[769]]) as usize,
+                Some(u16::from_be_bytes([data[770], data[771]]) as usize),
+            )
+        } else {
+            (256, None)
+        };
+
+        let colors = data[..768]
+            .chunks_exact(3)
+            .take(count.min(256))
+            .enumerate()
+            .map(|(i, c)| {
+                let alpha = if Some(i) == transparent { 0 } else { 0xFF };
+                RGBA8::new(c[0], c[1], c[2], alpha)
+            })
+            .collect();
+
+        Ok(Self { colors })
+    }
+
+    /// Write the palette as an Adobe Color Table. The first fully transparent
+    /// color is marked as the transparent index, other alpha values are lost.
+    pub fn write_act<T: Write>(&self, output: &mut T) -> Result<(), CzError> {
+        if self.colors.len() > 256 {
+            return Err(CzError::PaletteError);
+        }
+
+        for c in &self.colors {
+            output.write_all(&[c.r, c.g, c.b])?;
+        }
+        output.write_all(&vec![0u8; (256 - self.colors.len()) * 3])?;
+
+        let transparent = self
+            .colors
+            .iter()
+            .position(|c| c.a == 0)
+            .map_or(0xFFFF, |i| i as u16);
+
+        output.write_all(&(self.colors.len() as u16).to_be_bytes())?;
+        output.write_all(&transparent.to_be_bytes())?;
+
+        Ok(())
+    }
+
+    /// Read a palette from the RGBA pixels of a swatch image, one color per
+    /// pixel in order, keeping the first `len` colors. If the number of colors
+    /// is not known, the transparent black padding [`Palette::to_swatch()`]
+    /// adds to the last row is removed.
+    pub fn from_swatch(rgba: &[u8], len: Option<usize>) -> Result<Self, CzError> {
+        let mut colors: Vec<RGBA8> = rgba
+            .chunks_exact(4)
+            .map(|c| RGBA8::new(c[0], c[1], c[2], c[3]))
+            .collect();
+
+        match len {
+            Some(len) if len > colors.len() => return Err(CzError::PaletteError),
+            Some(len) => colors.truncate(len),
+            None => {
+                let padding = colors
+                    .iter()
+                    .rev()
+                    .take(SWATCH_WIDTH - 1)
+                    .take_while(|c| **c == RGBA8::new(0, 0, 0, 0))
+                    .count();
+                colors.truncate(colors.len() - padding);
+            }
+        }
+
+        if colors.len() > 256 {
+            return Err(CzError::PaletteError);
+        }
+
+        Ok(Self { colors })
+    }
+
+    /// Get the palette as the RGBA pixels of a swatch image with
+    /// [`SWATCH_WIDTH`] colors per row, along with the image dimensions
+    pub fn to_swatch(&self) -> (u32, u32, Vec<u8>) {
+        let height = self.colors.len().div_ceil(SWATCH_WIDTH).max(1);
+
+        let mut rgba: Vec<u8> = self
+            .colors
+            .iter()
+            .flat_map(|c| <[u8; 4]>::from(*c))
+            .collect();
+        rgba.resize(SWATCH_WIDTH * height * 4, 0);
+
+        (SWATCH_WIDTH as u32, height as u32, rgba)
+    }
+
+    /// Read a palette from a PNG swatch image. The number of colors is read
+    /// from the text chunk [`Palette::write_png()`] stores it in, if the
+    /// image still has it.
+    #[cfg(feature = "png")]
+    pub fn from_png<T: BufRead + Seek>(input: T) -> Result<Self, CzError> {
+        let mut decoder = png::Decoder::new(input);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info().map_err(std::io::Error::from)?;
+        let mut pixels = vec![0u8; reader.output_buffer_size().ok_or(CzError::PaletteError)?];
+        let frame = reader
+            .next_frame(&mut pixels)
+            .map_err(std::io::Error::from)?;
+        pixels.truncate(frame.buffer_size());
+        reader.finish().map_err(std::io::Error::from)?;
+
+        let rgba: Vec<u8> = match reader.output_color_type().0 {
+            png::ColorType::Rgba => pixels,
+            png::ColorType::Rgb => pixels
+                .chunks_exact(3)
+                .flat_map(|c| [c[0], c[1], c[2], 0xFF])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => pixels
+                .chunks_exact(2)
+                .flat_map(|c| [c[0], c[0], c[0], c[1]])
+                .collect(),
+            png::ColorType::Grayscale => pixels.iter().flat_map(|l| [*l, *l, *l, 0xFF]).collect(),
+            png::ColorType::Indexed => return Err(CzError::PaletteError),
+        };
+
+        let len = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .find(|t| t.keyword == SWATCH_LEN_KEY)
+            .and_then(|t| t.text.trim().parse().ok());
+
+        Self::from_swatch(&rgba, len)
+    }
+
+    /// Write the palette as a PNG swatch image with [`SWATCH_WIDTH`] colors
+    /// per row, storing the number of colors so the padding of the last row
+    /// is not read back as colors
+    #[cfg(feature = "png")]
+    pub fn write_png<T: Write>(&self, output: &mut T) -> Result<(), CzError> {
+        let (width, height, rgba) = self.to_swatch();
+
+        let mut encoder = png::Encoder::new(output, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .add_text_chunk(SWATCH_LEN_KEY.to_string(), self.colors.len().to_string())
+            .map_err(std::io::Error::from)?;
+
+        let mut writer = encoder.write_header().map_err(std::io::Error::from)?;
+        writer
+            .write_image_data(&rgba)
+            .map_err(std::io::Error::from)?;
+        writer.finish().map_err(std::io::Error::from)?;
+
+        Ok(())
+    }
+}
+
+/// A palette layout which stores an 8-bit grayscale image, such as the ones
+/// used for masks and transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrayscaleRamp {
+    /// White, with the index as the alpha value
+    Alpha,
+
+    /// Opaque gray, with the index as the luminance value
+    Luma,
+}
+
+impl GrayscaleRamp {
+    /// Get the color which a given value maps to in this ramp
+    pub fn color(&self, value: u8) -> RGBA8 {
+        match self {
+            Self::Alpha => RGBA8::new(0xFF, 0xFF, 0xFF, value),
+            Self::Luma => RGBA8::new(value, value, value, 0xFF),
+        }
+    }
+
+    /// Get the single channel value of an RGBA pixel in this ramp
+    pub fn value(&self, rgba: &[u8]) -> u8 {
+        match self {
+            Self::Alpha => rgba[3],
+            Self::Luma => rgba[0],
+        }
+    }
+
+    /// Build the full 256 color palette for this ramp
+    pub fn palette(&self) -> Palette {
+        Palette {
+            colors: (0..=0xFF).map(|i| self.color(i)).collect(),
+        }
+    }
+
+    /// Convert an RGBA bitmap into a single channel L8 bitmap
+    pub fn rgba_to_luma(&self, input: &[u8]) -> Vec<u8> {
+        input.chunks_exact(4).map(|p| self.value(p)).collect()
+    }
+
+    /// Convert a single channel L8 bitmap into an RGBA bitmap
+    pub fn luma_to_rgba(&self, input: &[u8]) -> Vec<u8> {
+        input
+            .iter()
+            .flat_map(|l| <[u8; 4]>::from(self.color(*l)))
+            .collect()
+    }
 }
 
 /// Get a palette from the input stream, beginning where the palette starts.
@@ -90,13 +548,20 @@ pub fn rgba_to_indexed(input: &[u8], palette: &Palette) -> Result<Vec<u8>, CzErr
 }
 
 /// Generate and a bitmap for a given input of RGBA pixels.
+///
+/// Without the `imagequant` feature the image is not quantized, and it must
+/// have 256 or fewer colors.
+#[cfg(feature = "imagequant")]
 pub fn indexed_gen_palette(
     input: &[u8],
     header: &CommonHeader,
 ) -> Result<(Vec<u8>, Vec<RGBA8>), CzError> {
-    let size = (header.width() as u32 * header.height() as u32) * 4;
+    let size = header.width() as usize * header.height() as usize * 4;
+    if input.len() > size {
+        return Err(CzError::BitmapFormat);
+    }
 
-    let mut buf: Vec<u8> = vec![0; size as usize];
+    let mut buf: Vec<u8> = vec![0; size];
     buf[..input.len()].copy_from_slice(input);
     let buf: Vec<imagequant::RGBA> = buf
         .windows(4)
@@ -104,16 +569,18 @@ pub fn indexed_gen_palette(
         .map(|c| imagequant::RGBA::new(c[0], c[1], c[2], c[3]))
         .collect();
 
+    let quant_error = |e: imagequant::Error| CzError::PaletteGen(e.to_string());
+
     let mut quant = Attributes::new();
-    quant.set_speed(1).unwrap();
+    quant.set_speed(1).map_err(quant_error)?;
 
     let mut image = quant
         .new_image(buf, header.width() as usize, header.height() as usize, 0.0)
-        .unwrap();
+        .map_err(quant_error)?;
 
-    let mut quant_result = quant.quantize(&mut image).unwrap();
+    let mut quant_result = quant.quantize(&mut image).map_err(quant_error)?;
 
-    let (palette, indicies) = quant_result.remapped(&mut image).unwrap();
+    let (palette, indicies) = quant_result.remapped(&mut image).map_err(quant_error)?;
 
     let gen_palette: Vec<RGBA8> = palette
         .iter()
@@ -126,12 +593,28 @@ pub fn indexed_gen_palette(
     Ok((indicies, output_palette))
 }
 
-pub fn _default_palette() -> Vec<RGBA8> {
-    let mut colormap = Vec::new();
-
-    for i in 0..=0xFF {
-        colormap.push(RGBA8::from([0xFF, 0xFF, 0xFF, i]))
+/// Generate and a bitmap for a given input of RGBA pixels.
+///
+/// Without the `imagequant` feature the image is not quantized, and it must
+/// have 256 or fewer colors.
+#[cfg(not(feature = "imagequant"))]
+pub fn indexed_gen_palette(
+    input: &[u8],
+    header: &CommonHeader,
+) -> Result<(Vec<u8>, Vec<RGBA8>), CzError> {
+    let size = header.width() as usize * header.height() as usize * 4;
+    if input.len() > size {
+        return Err(CzError::BitmapFormat);
     }
 
-    colormap
+    let mut buf: Vec<u8> = vec![0; size];
+    buf[..input.len()].copy_from_slice(input);
+
+    let palette = Palette::exact(&buf).ok_or(CzError::TooManyColors)?;
+    let indices = rgba_to_indexed(&buf, &palette)?;
+
+    let mut output_palette = palette.into_colors();
+    output_palette.resize(256, RGBA8::from([0, 0, 0, 0]));
+
+    Ok((indices, output_palette))
 }
@@ -1,13 +1,15 @@
 //! Shared types and traits between CZ# files
 
-use std::io::{self, Read, Seek, Write};
+use alloc::{format, string::String};
 
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use byteorder::LE;
 use thiserror::Error;
 
+use crate::io::{self, Read, ReadBytesExt, Seek, Write, WriteBytesExt};
+
 #[derive(Error, Debug)]
 pub enum CzError {
-    #[error("Expected CZ{}, got CZ{}", 0, 1)]
+    #[error("Expected CZ{0}, got CZ{1}")]
     VersionMismatch(u8, u8),
 
     #[error("Could not parse color index palette")]
@@ -16,9 +18,12 @@ pub enum CzError {
     #[error("Bitmap size does not match image size")]
     BitmapFormat,
 
-    #[error("CZ version is invalid: {}", 0)]
+    #[error("CZ version is invalid: {0}")]
     InvalidVersion(u32),
 
+    #[error("CZ{0} files cannot have a bit depth of {1}")]
+    UnsupportedDepth(u8, u16),
+
     #[error("File data is incorrect, it might be corrupt: {0}")]
     Corrupt(String),
 
@@ -30,6 +35,27 @@ pub enum CzError {
 
     #[error("Problem while decoding file")]
     DecodeError,
+
+    #[error("Header cannot be changed without re-encoding: {0}")]
+    HeaderEdit(String),
+
+    #[error("Image cannot be resized to {0}x{1}")]
+    InvalidSize(u16, u16),
+
+    #[error("Image has more than 256 colors, which needs the `imagequant` feature")]
+    TooManyColors,
+
+    #[error("CZ{0} files are not supported yet")]
+    UnsupportedVersion(u8),
+
+    #[error("Image has no pixels to compress")]
+    EmptyBitmap,
+
+    #[error("Could not generate a palette: {0}")]
+    PaletteGen(String),
+
+    #[error("Palette files must be GPL, ACT or PNG (with the `png` feature), not {0:?}")]
+    UnknownPaletteType(String),
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -125,7 +151,7 @@ impl CommonHeader {
         }
 
         // Ensure the version matches a CZ file type
-        let version = match CzVersion::try_from(magic[2] - b'0') {
+        let version = match CzVersion::try_from(magic[2].wrapping_sub(b'0')) {
             Ok(ver) => ver,
             Err(_) => return Err(CzError::NotCzFile),
         };
@@ -200,6 +226,10 @@ impl CommonHeader {
         self.unknown
     }
 
+    pub fn set_color_block(&mut self, color_block: u8) {
+        self.unknown = color_block
+    }
+
     pub fn write_into<T: Write>(&self, output: &mut T) -> Result<(), io::Error> {
         let magic_bytes = [b'C', b'Z', b'0' + self.version as u8, b'\0'];
 
@@ -268,6 +298,24 @@ impl ExtendedHeader {
         }
     }
 
+    /// The unknown byte at the start of the header
+    pub fn unknown_1(&self) -> u8 {
+        self.unknown_1
+    }
+
+    /// The unknown value at the end of the longer header variant
+    pub fn unknown_2(&self) -> Option<u32> {
+        self.unknown_2
+    }
+
+    pub fn set_unknown_1(&mut self, unknown_1: u8) {
+        self.unknown_1 = unknown_1
+    }
+
+    pub fn set_unknown_2(&mut self, unknown_2: Option<u32>) {
+        self.unknown_2 = unknown_2
+    }
+
     pub fn with_crop(mut self, crop: (u16, u16)) -> Self {
         self.crop_width = crop.0;
         self.crop_height = crop.1;
@@ -289,6 +337,28 @@ impl ExtendedHeader {
         self
     }
 
+    /// Scale the offset, crop, bounds and unknown size of the header, so an
+    /// image resized from `old_size` to `new_size` is placed the same way.
+    pub fn scale(&mut self, old_size: (u16, u16), new_size: (u16, u16)) {
+        let scale = |value: u16, old: u16, new: u16| {
+            let scaled = (value as u32 * new as u32 + old as u32 / 2) / old.max(1) as u32;
+            scaled.min(u16::MAX as u32) as u16
+        };
+        let (x, y) = (
+            |v| scale(v, old_size.0, new_size.0),
+            |v| scale(v, old_size.1, new_size.1),
+        );
+
+        self.offset_x = x(self.offset_x);
+        self.offset_y = y(self.offset_y);
+        self.crop_width = x(self.crop_width);
+        self.crop_height = y(self.crop_height);
+        self.bounds_width = x(self.bounds_width);
+        self.bounds_height = y(self.bounds_height);
+        self.unknown_width = self.unknown_width.map(x);
+        self.unknown_height = self.unknown_height.map(y);
+    }
+
     pub fn from_bytes<T: Seek + Read>(
         input: &mut T,
         common_header: &CommonHeader,
@@ -344,8 +414,8 @@ impl ExtendedHeader {
 
         if let Some(width) = self.unknown_width {
             output.write_u16::<LE>(width)?;
-            output.write_u16::<LE>(self.unknown_height.unwrap())?;
-            output.write_u32::<LE>(self.unknown_2.unwrap())?;
+            output.write_u16::<LE>(self.unknown_height.unwrap_or_default())?;
+            output.write_u32::<LE>(self.unknown_2.unwrap_or_default())?;
         }
 
         Ok(())
@@ -1,11 +1,14 @@
-use std::{
-    collections::HashMap,
-    io::{Read, Seek, Write},
-};
+use alloc::{format, string::String, vec, vec::Vec};
 
 use crate::binio::BitIo;
 use crate::common::CzError;
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use crate::io::{self, Read, ReadBytesExt, Seek, Write, WriteBytesExt};
+use byteorder::LE;
+use hashbrown::HashMap;
+
+/// Largest amount of memory reserved up front for a decompressed chunk, as
+/// the sizes in the chunk info of a corrupt file can be far too large
+const MAX_CHUNK_CAPACITY: usize = 0x1000000;
 
 /// The size of compressed data in each chunk
 #[derive(Debug, Clone, Copy)]
@@ -37,7 +40,7 @@ pub struct CompressionInfo {
 }
 
 impl CompressionInfo {
-    pub fn write_into<T: Write>(&self, output: &mut T) -> Result<(), std::io::Error> {
+    pub fn write_into<T: Write>(&self, output: &mut T) -> Result<(), io::Error> {
         output.write_u32::<LE>(self.chunk_count as u32)?;
 
         for chunk in &self.chunks {
@@ -91,13 +94,19 @@ pub fn decompress<T: Seek + Read>(
     let mut output_buf: Vec<u8> = vec![];
 
     for block in &chunk_info.chunks {
-        let mut buffer = vec![0u16; block.size_compressed];
+        let length = block
+            .size_compressed
+            .checked_mul(2)
+            .ok_or(CzError::Corrupt(String::from(
+                "Compressed chunk is too large",
+            )))?;
 
-        for word in buffer.iter_mut() {
-            *word = input.read_u16::<LE>().unwrap();
-        }
+        let buffer: Vec<u16> = read_chunk(input, length)?
+            .chunks_exact(2)
+            .map(|w| u16::from_le_bytes([w[0], w[1]]))
+            .collect();
 
-        let raw_buf = decompress_lzw(&buffer, block.size_raw);
+        let raw_buf = decompress_lzw(&buffer, block.size_raw)?;
 
         output_buf.write_all(&raw_buf)?;
     }
@@ -105,7 +114,22 @@ pub fn decompress<T: Seek + Read>(
     Ok(output_buf)
 }
 
-fn decompress_lzw(input_data: &[u16], size: usize) -> Vec<u8> {
+/// Read the compressed data of a chunk, without allocating all of it up front
+/// in case the size is corrupt
+fn read_chunk<T: Read>(input: &mut T, length: usize) -> Result<Vec<u8>, CzError> {
+    let mut buffer = Vec::with_capacity(length.min(MAX_CHUNK_CAPACITY));
+    let mut block = [0u8; 0x1000];
+
+    while buffer.len() < length {
+        let block_len = (length - buffer.len()).min(block.len());
+        input.read_exact(&mut block[..block_len])?;
+        buffer.extend_from_slice(&block[..block_len]);
+    }
+
+    Ok(buffer)
+}
+
+fn decompress_lzw(input_data: &[u16], size: usize) -> Result<Vec<u8>, CzError> {
     let mut dictionary: HashMap<u16, Vec<u8>> = HashMap::new();
     for i in 0..256 {
         dictionary.insert(i as u16, vec![i as u8]);
@@ -113,9 +137,9 @@ fn decompress_lzw(input_data: &[u16], size: usize) -> Vec<u8> {
     let mut dictionary_count = dictionary.len() as u16;
 
     let mut w = vec![0];
-    let mut result = Vec::with_capacity(size);
+    let mut result = Vec::with_capacity(size.min(MAX_CHUNK_CAPACITY));
 
-    input_data.iter().for_each(|element| {
+    for element in input_data {
         let mut entry;
         if let Some(x) = dictionary.get(element) {
             entry = x.clone();
@@ -123,19 +147,22 @@ fn decompress_lzw(input_data: &[u16], size: usize) -> Vec<u8> {
             entry = w.clone();
             entry.push(w[0]);
         } else {
-            panic!("Bad compressed element: {}", element)
+            return Err(CzError::Corrupt(format!(
+                "Bad compressed element: {}",
+                element
+            )));
         }
 
-        result.write_all(&entry).unwrap();
+        result.extend_from_slice(&entry);
         w.push(entry[0]);
 
         dictionary.insert(dictionary_count, w.clone());
-        dictionary_count += 1;
+        dictionary_count = dictionary_count.wrapping_add(1);
 
         w = entry;
-    });
+    }
 
-    result
+    Ok(result)
 }
 
 /// Decompress an LZW compressed stream like CZ2
@@ -146,33 +173,36 @@ pub fn decompress2<T: Seek + Read>(
     let mut output_buf: Vec<u8> = vec![];
 
     for block in &chunk_info.chunks {
-        let mut buffer = vec![0u8; block.size_compressed];
-        input.read_exact(&mut buffer).unwrap();
+        let buffer = read_chunk(input, block.size_compressed)?;
 
-        let raw_buf = decompress_lzw2(&buffer, block.size_raw);
+        let raw_buf = decompress_lzw2(&buffer, block.size_raw)?;
 
-        output_buf.write_all(&raw_buf).unwrap();
+        output_buf.write_all(&raw_buf)?;
     }
 
     Ok(output_buf)
 }
 
-fn decompress_lzw2(input_data: &[u8], size: usize) -> Vec<u8> {
-    let data = input_data;
+fn decompress_lzw2(input_data: &[u8], size: usize) -> Result<Vec<u8>, CzError> {
     let mut dictionary = HashMap::new();
     for i in 0..256 {
         dictionary.insert(i as u64, vec![i as u8]);
     }
     let mut dictionary_count = dictionary.len() as u64;
-    let mut result = Vec::with_capacity(size);
+    let mut result = Vec::with_capacity(size.min(MAX_CHUNK_CAPACITY));
 
+    // The last element can run past the end of the data, which is read as
+    // zeros instead
     let data_size = input_data.len();
-    let mut bit_io = BitIo::new(data.to_vec());
-    let mut w = dictionary.get(&0).unwrap().clone();
+    let mut data = input_data.to_vec();
+    data.extend_from_slice(&[0; 4]);
+
+    let mut bit_io = BitIo::new(data);
+    let mut w = vec![0];
 
     let mut element;
     loop {
-        if bit_io.byte_offset() >= data_size - 1 {
+        if bit_io.byte_offset() >= data_size.saturating_sub(1) {
             break;
         }
 
@@ -191,25 +221,24 @@ fn decompress_lzw2(input_data: &[u8], size: usize) -> Vec<u8> {
             entry = w.clone();
             entry.push(w[0])
         } else {
-            panic!(
+            return Err(CzError::Corrupt(format!(
                 "Bad compressed element {} at offset {}",
                 element,
                 bit_io.byte_offset()
-            )
+            )));
         }
 
-        //println!("{}", element);
-
-        result.write_all(&entry).unwrap();
+        result.extend_from_slice(&entry);
         w.push(entry[0]);
         dictionary.insert(dictionary_count, w.clone());
         dictionary_count += 1;
         w.clone_from(&entry);
     }
-    result
+
+    Ok(result)
 }
 
-pub fn compress(data: &[u8], size: usize) -> (Vec<u8>, CompressionInfo) {
+pub fn compress(data: &[u8], size: usize) -> Result<(Vec<u8>, CompressionInfo), CzError> {
     let mut size = size;
     if size == 0 {
         size = 0xFEFD
@@ -247,7 +276,7 @@ pub fn compress(data: &[u8], size: usize) -> (Vec<u8>, CompressionInfo) {
     }
 
     if output_info.chunk_count == 0 {
-        panic!("No chunks compressed!")
+        return Err(CzError::EmptyBitmap);
     } else if output_info.chunk_count != 1 {
         output_info.chunks[0].size_raw -= 1;
         output_info.chunks[output_info.chunk_count - 1].size_raw += 1;
@@ -255,7 +284,7 @@ pub fn compress(data: &[u8], size: usize) -> (Vec<u8>, CompressionInfo) {
 
     output_info.total_size_compressed = output_buf.len() / 2;
 
-    (output_buf, output_info)
+    Ok((output_buf, output_info))
 }
 
 fn compress_lzw(data: &[u8], size: usize, last: Vec<u8>) -> (usize, Vec<u16>, Vec<u8>) {
@@ -310,7 +339,7 @@ fn compress_lzw(data: &[u8], size: usize, last: Vec<u8>) -> (usize, Vec<u16>, Ve
     (count, compressed, last_element)
 }
 
-pub fn compress2(data: &[u8]) -> (Vec<u8>, CompressionInfo) {
+pub fn compress2(data: &[u8]) -> Result<(Vec<u8>, CompressionInfo), CzError> {
     let mut part_data;
 
     let mut offset = 0;
@@ -341,11 +370,11 @@ pub fn compress2(data: &[u8]) -> (Vec<u8>, CompressionInfo) {
     }
 
     if output_info.chunk_count == 0 {
-        panic!("No chunks compressed!")
+        return Err(CzError::EmptyBitmap);
     }
 
     output_info.total_size_compressed = output_buf.len();
-    (output_buf, output_info)
+    Ok((output_buf, output_info))
 }
 
 fn compress_lzw2(data: &[u8], last: Vec<u8>) -> (usize, Vec<u8>, Vec<u8>) {
@@ -0,0 +1,199 @@
+//! Pixel and header comparison between images
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{common::CzError, dynamic::CzFile};
+
+/// Color used for changed pixels in [`BitmapDiff::highlight()`]
+const HIGHLIGHT_COLOR: [u8; 4] = [0xFF, 0x00, 0xFF, 0xFF];
+
+/// The pixels which differ between two RGBA bitmaps.
+///
+/// Bitmaps of different sizes are compared with their top left corners
+/// aligned, and pixels which only exist in one of them count as changed.
+#[derive(Debug, Clone)]
+pub struct BitmapDiff {
+    width: u32,
+    height: u32,
+
+    /// Whether each pixel in the compared area changed
+    changed: Vec<bool>,
+}
+
+impl BitmapDiff {
+    /// Compare two RGBA bitmaps, given with their width and height
+    pub fn new(
+        left: &[u8],
+        left_size: (u32, u32),
+        right: &[u8],
+        right_size: (u32, u32),
+    ) -> Result<Self, CzError> {
+        if left.len() != left_size.0 as usize * left_size.1 as usize * 4
+            || right.len() != right_size.0 as usize * right_size.1 as usize * 4
+        {
+            return Err(CzError::BitmapFormat);
+        }
+
+        let width = left_size.0.max(right_size.0);
+        let height = left_size.1.max(right_size.1);
+
+        fn pixel(bitmap: &[u8], size: (u32, u32), x: u32, y: u32) -> Option<&[u8]> {
+            if x >= size.0 || y >= size.1 {
+                return None;
+            }
+
+            let i = (y as usize * size.0 as usize + x as usize) * 4;
+            Some(&bitmap[i..i + 4])
+        }
+
+        let mut changed = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                changed.push(pixel(left, left_size, x, y) != pixel(right, right_size, x, y));
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            changed,
+        })
+    }
+
+    /// Compare the bitmaps of two CZ files
+    pub fn from_cz(left: &CzFile, right: &CzFile) -> Result<Self, CzError> {
+        let size = |cz: &CzFile| (cz.header().width() as u32, cz.header().height() as u32);
+
+        Self::new(left.as_raw(), size(left), right.as_raw(), size(right))
+    }
+
+    /// Width and height of the compared area, which covers both bitmaps
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Number of pixels which changed
+    pub fn changed_pixels(&self) -> usize {
+        self.changed.iter().filter(|c| **c).count()
+    }
+
+    /// Whether the bitmaps are exactly the same
+    pub fn is_identical(&self) -> bool {
+        !self.changed.contains(&true)
+    }
+
+    /// Whether the pixel at a position changed
+    pub fn is_changed(&self, x: u32, y: u32) -> bool {
+        x < self.width
+            && y < self.height
+            && self.changed[y as usize * self.width as usize + x as usize]
+    }
+
+    /// The smallest rectangle containing all of the changed pixels, as
+    /// `(x, y, width, height)`, or [`None`] if nothing changed
+    pub fn bounding_box(&self) -> Option<(u32, u32, u32, u32)> {
+        let mut min = (u32::MAX, u32::MAX);
+        let mut max = (0, 0);
+
+        for (i, _) in self.changed.iter().enumerate().filter(|(_, c)| **c) {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+
+        if min.0 == u32::MAX {
+            return None;
+        }
+
+        Some((min.0, min.1, max.0 - min.0 + 1, max.1 - min.1 + 1))
+    }
+
+    /// Create an RGBA bitmap the size of the compared area which shows a
+    /// faded version of a base bitmap, with the changed pixels highlighted
+    pub fn highlight(&self, base: &[u8], base_size: (u32, u32)) -> Vec<u8> {
+        let mut output = vec![0u8; self.width as usize * self.height as usize * 4];
+
+        for (i, out) in output.chunks_exact_mut(4).enumerate() {
+            if self.changed[i] {
+                out.copy_from_slice(&HIGHLIGHT_COLOR);
+                continue;
+            }
+
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            if x >= base_size.0 || y >= base_size.1 {
+                continue;
+            }
+
+            // Fade the unchanged pixels to gray so the highlight stands out
+            let p = (y as usize * base_size.0 as usize + x as usize) * 4;
+            let luma =
+                (base[p] as u32 * 299 + base[p + 1] as u32 * 587 + base[p + 2] as u32 * 114) / 1000;
+            let gray = (luma / 2 + 0x40) as u8;
+            out.copy_from_slice(&[gray, gray, gray, base[p + 3].max(0x40)]);
+        }
+
+        output
+    }
+}
+
+/// A header field which differs between two CZ files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderDifference {
+    /// Name of the field
+    pub field: &'static str,
+
+    /// Value in the first file, or [`None`] if it does not have the field
+    pub left: Option<String>,
+
+    /// Value in the second file, or [`None`] if it does not have the field
+    pub right: Option<String>,
+}
+
+/// List the header fields, including the extended header and palette size,
+/// which differ between two CZ files
+pub fn header_differences(left: &CzFile, right: &CzFile) -> Vec<HeaderDifference> {
+    let fields = |cz: &CzFile| {
+        let header = cz.header();
+        let ext = *cz.extended_header();
+        let pair = |a: u16, b: u16| format!("{}x{}", a, b);
+
+        [
+            ("version", Some(format!("{:?}", header.version()))),
+            ("size", Some(pair(header.width(), header.height()))),
+            ("depth", Some(header.depth().to_string())),
+            ("color block", Some(header.color_block().to_string())),
+            ("offset", ext.map(|e| pair(e.offset_x, e.offset_y))),
+            ("crop", ext.map(|e| pair(e.crop_width, e.crop_height))),
+            ("bounds", ext.map(|e| pair(e.bounds_width, e.bounds_height))),
+            (
+                "unknown size",
+                ext.and_then(|e| Some(pair(e.unknown_width?, e.unknown_height?))),
+            ),
+            ("unknown 1", ext.map(|e| e.unknown_1().to_string())),
+            (
+                "unknown 2",
+                ext.and_then(|e| e.unknown_2()).map(|u| u.to_string()),
+            ),
+            (
+                "palette size",
+                cz.palette().as_ref().map(|p| p.len().to_string()),
+            ),
+        ]
+    };
+
+    fields(left)
+        .into_iter()
+        .zip(fields(right))
+        .filter(|(l, r)| l.1 != r.1)
+        .map(|(l, r)| HeaderDifference {
+            field: l.0,
+            left: l.1,
+            right: r.1,
+        })
+        .collect()
+}
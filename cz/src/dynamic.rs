@@ -1,15 +1,19 @@
-use byteorder::ReadBytesExt;
-use log::debug;
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::Display;
+use log::{debug, error};
 use rgb::ComponentSlice;
-use std::{
-    fs::File,
-    io::{BufWriter, Read, Seek, SeekFrom, Write},
-};
+#[cfg(feature = "std")]
+use std::{fs::File, io::BufWriter};
 
 use crate::{
-    color::{get_palette, indexed_gen_palette, indexed_to_rgba, rgba_to_indexed, Palette},
+    color::{
+        GrayscaleRamp, Palette, choose_depth, indexed_gen_palette, indexed_to_rgba, rgba_to_indexed,
+    },
     common::{CommonHeader, CzError, CzVersion, ExtendedHeader},
     formats::{cz0, cz1, cz2, cz3, cz4},
+    info::read_headers,
+    io::{Cursor, Read, ReadBytesExt, Seek, Write},
+    resize::{ResizeFilter, resize_rgba},
 };
 
 /// A CZ# interface which can open and save any CZ file type.
@@ -21,6 +25,10 @@ pub struct CzFile {
     /// A palette of RGBA values for indexed color
     palette: Option<Palette>,
 
+    /// The palette indices of an indexed color image, kept so they can be
+    /// written back unchanged
+    indices: Option<Vec<u8>>,
+
     /// 32bpp RGBA bitmap representation of the file contents
     bitmap: Vec<u8>,
 }
@@ -32,24 +40,15 @@ impl CzFile {
     /// [magic bytes](https://en.wikipedia.org/wiki/File_format#Magic_number)
     /// of the file
     pub fn decode<T: Seek + ReadBytesExt + Read>(input: &mut T) -> Result<Self, CzError> {
-        // Get the header common to all CZ images
-        let header_common = CommonHeader::from_bytes(input)?;
-        let mut header_extended = None;
-        if header_common.length() > 15 && header_common.version() != CzVersion::CZ2 {
-            header_extended = Some(ExtendedHeader::from_bytes(input, &header_common)?);
-        }
-        input.seek(SeekFrom::Start(header_common.length() as u64))?;
+        // Get the headers and the color palette if there is one
+        let (header_common, header_extended, palette) = read_headers(input)?;
 
         debug!("{:?}", header_common);
         debug!("{:?}", header_extended);
 
-        // Get the color palette if the bit depth is 8 or less
-        let palette = if header_common.depth() <= 8 {
-            let color_count = 1 << header_common.depth();
-            Some(get_palette(input, color_count)?)
-        } else {
-            None
-        };
+        if header_common.depth() == 4 {
+            return Err(CzError::UnsupportedDepth(header_common.version() as u8, 4));
+        }
 
         // Get the image data as a bitmap
         let mut bitmap = match header_common.version() {
@@ -58,13 +57,13 @@ impl CzFile {
             CzVersion::CZ2 => cz2::decode(input)?,
             CzVersion::CZ3 => cz3::decode(input, &header_common)?,
             CzVersion::CZ4 => cz4::decode(input, &header_common)?,
-            CzVersion::CZ5 => unimplemented!("CZ5 files are not implemented! Please contact the application developers about this file."),
+            CzVersion::CZ5 => return Err(CzError::UnsupportedVersion(5)),
         };
 
         let image_size = header_common.width() as usize * header_common.height() as usize;
         if bitmap.len() != image_size * (header_common.depth() >> 3) as usize {
             // If the bitmap is smaller or larger than the image size, it is likely wrong
-            eprintln!(
+            error!(
                 "Image is wrong, length is {}, expected {}",
                 bitmap.len(),
                 image_size * (header_common.depth() >> 3) as usize
@@ -72,13 +71,13 @@ impl CzFile {
             return Err(CzError::Corrupt(String::from("Bitmap size incorrect")));
         }
 
+        let mut indices = None;
         match header_common.depth() {
-            4 => {
-                todo!("Files with a bit depth of 4 are not yet supported")
-            }
             8 => {
                 if let Some(palette) = &palette {
-                    bitmap = indexed_to_rgba(&bitmap, palette)?;
+                    let rgba = indexed_to_rgba(&bitmap, palette)?;
+                    indices = Some(bitmap);
+                    bitmap = rgba;
                 } else {
                     return Err(CzError::PaletteError);
                 }
@@ -95,7 +94,7 @@ impl CzFile {
                 return Err(CzError::Corrupt(format!(
                     "Invalid bit depth: {}",
                     header_common.depth()
-                )))
+                )));
             }
         }
 
@@ -103,6 +102,7 @@ impl CzFile {
             header_common,
             header_extended,
             palette,
+            indices,
             bitmap,
         })
     }
@@ -110,6 +110,7 @@ impl CzFile {
     /// Save the `DynamicCz` as a CZ# file. The format saved in is determined
     /// from the format in the header. Check [`CommonHeader::set_version()`]
     /// to change the CZ# version.
+    #[cfg(feature = "std")]
     pub fn save_as_cz<P: ?Sized + AsRef<std::path::Path>>(&self, path: &P) -> Result<(), CzError> {
         let mut out_file = BufWriter::new(File::create(path.as_ref())?);
 
@@ -127,6 +128,10 @@ impl CzFile {
         let mut header = *self.header();
         debug!("{:?}", header);
 
+        if self.bitmap.len() != header.width() as usize * header.height() as usize * 4 {
+            return Err(CzError::BitmapFormat);
+        }
+
         if header.version() == CzVersion::CZ2 {
             header.set_length(0x12)
         }
@@ -140,7 +145,9 @@ impl CzFile {
         }
 
         // Pad out the header to the stated length
-        if let Ok(p) = output.stream_position() && (p as usize) < header.length() {
+        if let Ok(p) = output.stream_position()
+            && (p as usize) < header.length()
+        {
             let padding_length = header.length() - p as usize;
             let _ = output.write_all(&vec![0u8; padding_length]);
         }
@@ -148,18 +155,28 @@ impl CzFile {
         let output_bitmap;
         match header.depth() {
             4 => {
-                eprintln!("Files with a bit depth of 4 are not yet supported");
-                todo!()
+                return Err(CzError::UnsupportedDepth(header.version() as u8, 4));
             }
             8 => {
                 // Do things with palettes
                 if let Some(pal) = &self.palette {
-                    // Use the existing palette to palette the image
-                    output_bitmap = rgba_to_indexed(self.as_raw(), pal)?;
+                    if pal.len() > 256 {
+                        return Err(CzError::PaletteError);
+                    }
+
+                    // Use the existing indices if there are any, otherwise
+                    // use the existing palette to palette the image
+                    output_bitmap = match &self.indices {
+                        Some(indices) => indices.clone(),
+                        None => rgba_to_indexed(self.as_raw(), pal)?,
+                    };
 
                     for rgba in pal.colors() {
                         output.write_all(rgba.as_slice())?;
                     }
+
+                    // Pad out smaller palettes to the full color count
+                    output.write_all(&vec![0u8; (256 - pal.len()) * 4])?;
                 } else {
                     // Generate a palette and corresponding indexed bitmap if there is none
                     let result = indexed_gen_palette(self.as_raw(), self.header())?;
@@ -187,7 +204,7 @@ impl CzFile {
                 return Err(CzError::Corrupt(format!(
                     "Invalid bit depth: {}",
                     self.header_common.depth()
-                )))
+                )));
             }
         }
 
@@ -197,7 +214,7 @@ impl CzFile {
             CzVersion::CZ2 => cz2::encode(&mut output, &output_bitmap)?,
             CzVersion::CZ3 => cz3::encode(&mut output, &output_bitmap, &self.header_common)?,
             CzVersion::CZ4 => cz4::encode(&mut output, &output_bitmap, &self.header_common)?,
-            CzVersion::CZ5 => todo!(),
+            CzVersion::CZ5 => return Err(CzError::UnsupportedVersion(5)),
         }
 
         Ok(())
@@ -212,10 +229,267 @@ impl CzFile {
             header_common,
             header_extended: None,
             palette: None,
+            indices: None,
             bitmap,
         }
     }
 
+    /// Create an 8-bit CZ# image from palette indices and the [`Palette`]
+    /// they refer to. The indices and palette are saved unchanged.
+    pub fn from_indexed(
+        version: CzVersion,
+        width: u16,
+        height: u16,
+        indices: Vec<u8>,
+        palette: Palette,
+    ) -> Result<Self, CzError> {
+        let mut header_common = CommonHeader::new(version, width, height);
+        header_common.set_depth(8);
+
+        Ok(Self {
+            header_common,
+            header_extended: None,
+            bitmap: indexed_to_rgba(&indices, &palette)?,
+            palette: Some(palette),
+            indices: Some(indices),
+        })
+    }
+
+    /// Create an 8-bit CZ# image from single channel L8 bytes. The palette is
+    /// set to the given [`GrayscaleRamp`], so no quantization happens on save.
+    pub fn from_luma8(
+        version: CzVersion,
+        width: u16,
+        height: u16,
+        luma: &[u8],
+        ramp: GrayscaleRamp,
+    ) -> Result<Self, CzError> {
+        if luma.len() != width as usize * height as usize {
+            return Err(CzError::BitmapFormat);
+        }
+
+        let mut header_common = CommonHeader::new(version, width, height);
+        header_common.set_depth(8);
+
+        Ok(Self {
+            header_common,
+            header_extended: None,
+            palette: Some(ramp.palette()),
+            indices: Some(luma.to_vec()),
+            bitmap: ramp.luma_to_rgba(luma),
+        })
+    }
+
+    /// Convert the image to another CZ# version and bit depth, keeping the
+    /// extended header and palette wherever the new format allows it.
+    ///
+    /// Returns a list of everything which could not be carried over.
+    pub fn convert(
+        &mut self,
+        version: CzVersion,
+        depth: u16,
+    ) -> Result<Vec<ConversionLoss>, CzError> {
+        if version == CzVersion::CZ5 {
+            return Err(CzError::InvalidVersion(5));
+        }
+
+        // CZ4 stores the color and alpha separately, so it is always 32-bit
+        if !(depth == 8 || depth == 24 || depth == 32) || (version == CzVersion::CZ4 && depth != 32)
+        {
+            return Err(CzError::UnsupportedDepth(version as u8, depth));
+        }
+
+        let mut losses = Vec::new();
+
+        if version == CzVersion::CZ2 && self.header_extended.is_some() {
+            self.header_extended = None;
+            losses.push(ConversionLoss::ExtendedHeader);
+        }
+
+        if depth != 8 && self.palette.is_some() {
+            self.clear_palette();
+            losses.push(ConversionLoss::Palette);
+        } else if depth == 8 && self.palette.is_none() {
+            losses.push(ConversionLoss::Quantized);
+        }
+
+        if depth == 24 && self.bitmap.chunks_exact(4).any(|p| p[3] != 0xFF) {
+            losses.push(ConversionLoss::Alpha);
+        }
+
+        // The header length has to match the extended header of the new file
+        let length = match self.header_extended {
+            Some(ext) if ext.unknown_width.is_some() => 36,
+            Some(_) => 28,
+            None => 15,
+        };
+        self.header_common.set_length(length);
+        self.header_common.set_version(version as u8)?;
+        self.header_common.set_depth(depth);
+
+        Ok(losses)
+    }
+
+    /// Set the bit depth chosen by [`choose_depth()`] for the image, and
+    /// return it. CZ4 files are always 32-bit.
+    ///
+    /// When 8-bit is chosen the existing palette is kept if it has all of the
+    /// colors of the image, otherwise it is replaced by a palette of exactly
+    /// those colors, so nothing is quantized.
+    pub fn set_auto_depth(&mut self) -> u16 {
+        let depth = if self.header_common.version() == CzVersion::CZ4 {
+            32
+        } else {
+            choose_depth(&self.bitmap)
+        };
+
+        if depth != 8 {
+            self.clear_palette();
+        } else if !self
+            .palette
+            .as_ref()
+            .is_some_and(|p| p.covers(&self.bitmap))
+        {
+            self.indices = None;
+            self.palette = Palette::exact(&self.bitmap);
+        }
+
+        self.header_common.set_depth(depth);
+
+        depth
+    }
+
+    /// Find the CZ version and bit depth which give the smallest file while
+    /// storing exactly the same pixels, keeping the extended header.
+    ///
+    /// Every candidate is encoded and decoded again, and only kept if the
+    /// decoded bitmap is identical to this one. Returns [`None`] if no
+    /// candidate could be verified.
+    pub fn optimize(&self) -> Option<Optimized> {
+        let mut depths = vec![32];
+        if self.bitmap.chunks_exact(4).all(|p| p[3] == 0xFF) {
+            depths.push(24);
+        }
+
+        // 8-bit candidates use the existing palette if it has all the colors
+        let palette_covers = self
+            .palette
+            .as_ref()
+            .is_some_and(|p| p.covers(&self.bitmap));
+        let palette = if palette_covers {
+            self.palette.clone()
+        } else {
+            Palette::exact(&self.bitmap)
+        };
+        if palette.is_some() {
+            depths.push(8);
+        }
+
+        let mut smallest: Option<Optimized> = None;
+        for version in [
+            CzVersion::CZ0,
+            CzVersion::CZ1,
+            CzVersion::CZ2,
+            CzVersion::CZ3,
+            CzVersion::CZ4,
+        ] {
+            for &depth in &depths {
+                let mut candidate = self.clone();
+                if depth == 8 && !palette_covers {
+                    candidate.indices = None;
+                    candidate.palette = palette.clone();
+                }
+
+                match candidate.convert(version, depth) {
+                    Ok(losses) if !losses.contains(&ConversionLoss::ExtendedHeader) => (),
+                    _ => continue,
+                }
+
+                let mut bytes = Cursor::new(Vec::new());
+                if candidate.encode(&mut bytes).is_err() {
+                    continue;
+                }
+                let bytes = bytes.into_inner();
+
+                if smallest
+                    .as_ref()
+                    .is_some_and(|s| s.bytes.len() <= bytes.len())
+                {
+                    continue;
+                }
+
+                let verified = CzFile::decode(&mut Cursor::new(&bytes))
+                    .is_ok_and(|decoded| decoded.bitmap == self.bitmap);
+
+                if verified {
+                    smallest = Some(Optimized {
+                        cz: candidate,
+                        bytes,
+                    });
+                }
+            }
+        }
+
+        smallest
+    }
+
+    /// Resize the image with the given filter. The extended header offset,
+    /// crop and bounds are scaled by the same amount, so the engine places
+    /// the image the same way relative to its new size.
+    ///
+    /// Grayscale images, such as masks, are resized as a single channel and
+    /// keep their [`GrayscaleRamp`] palette. For other images the palette is
+    /// kept if it still has every color of the resized image, otherwise it is
+    /// cleared and generated again on save.
+    pub fn resize(&mut self, width: u16, height: u16, filter: ResizeFilter) -> Result<(), CzError> {
+        if width == 0 || height == 0 {
+            return Err(CzError::InvalidSize(width, height));
+        }
+
+        let old_size = (self.header_common.width(), self.header_common.height());
+        let resize = |bitmap: &[u8]| {
+            resize_rgba(
+                bitmap,
+                (old_size.0 as usize, old_size.1 as usize),
+                (width as usize, height as usize),
+                filter,
+            )
+        };
+
+        // The header only changes once the resized bitmap has been made
+        if let Some(luma) = self.to_luma8() {
+            let gray: Vec<u8> = luma.iter().flat_map(|l| [*l, *l, *l, 0xFF]).collect();
+            let resized: Vec<u8> = resize(&gray)?.chunks_exact(4).map(|p| p[0]).collect();
+
+            self.header_common.set_width(width);
+            self.header_common.set_height(height);
+            if let Err(e) = self.set_luma8(&resized) {
+                self.header_common.set_width(old_size.0);
+                self.header_common.set_height(old_size.1);
+                return Err(e);
+            }
+        } else {
+            let resized = resize(&self.bitmap)?;
+
+            self.header_common.set_width(width);
+            self.header_common.set_height(height);
+            self.set_bitmap(resized);
+            if !self
+                .palette
+                .as_ref()
+                .is_some_and(|p| p.covers(&self.bitmap))
+            {
+                self.palette = None;
+            }
+        }
+
+        if let Some(ext) = &mut self.header_extended {
+            ext.scale(old_size, (width, height));
+        }
+
+        Ok(())
+    }
+
     /// Set a specific header for the image.
     pub fn with_header(mut self, header: CommonHeader) -> Self {
         self.header_common = header;
@@ -248,6 +522,10 @@ impl CzFile {
     }
 
     /// Returns a mutable reference to the palette if it exists.
+    ///
+    /// For images which have palette indices, the indices are kept and saved
+    /// with the modified palette. Use [`CzFile::refresh_bitmap()`] to update
+    /// the RGBA bitmap with the modified palette.
     pub fn palette_mut(&mut self) -> &mut Option<Palette> {
         &mut self.palette
     }
@@ -255,7 +533,74 @@ impl CzFile {
     /// Remove the image palette, which forces palette regeneration on save
     /// for images with a bit depth of 8.
     pub fn clear_palette(&mut self) {
-        *self.palette_mut() = None
+        *self.palette_mut() = None;
+        self.indices = None;
+    }
+
+    /// Rebuild the RGBA bitmap from the palette indices and the current
+    /// palette, if the image has both. This does nothing otherwise.
+    pub fn refresh_bitmap(&mut self) -> Result<(), CzError> {
+        if let (Some(indices), Some(palette)) = (&self.indices, &self.palette) {
+            self.bitmap = indexed_to_rgba(indices, palette)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the palette indices of the image if it is an indexed color
+    /// image with a palette.
+    pub fn indices(&self) -> Option<&Vec<u8>> {
+        self.palette.as_ref()?;
+
+        self.indices.as_ref()
+    }
+
+    /// Replace the bitmap with palette indices and the [`Palette`] they refer
+    /// to, which are saved unchanged for images with a bit depth of 8.
+    pub fn set_indexed(&mut self, indices: Vec<u8>, palette: Palette) -> Result<(), CzError> {
+        self.bitmap = indexed_to_rgba(&indices, &palette)?;
+        self.palette = Some(palette);
+        self.indices = Some(indices);
+
+        Ok(())
+    }
+
+    /// Returns the kind of [`GrayscaleRamp`] the palette forms if this is an
+    /// 8-bit grayscale image, such as a mask or transition.
+    pub fn grayscale_ramp(&self) -> Option<GrayscaleRamp> {
+        if self.header_common.depth() != 8 {
+            return None;
+        }
+
+        self.palette.as_ref()?.grayscale_ramp()
+    }
+
+    /// Returns the image as a single channel L8 bitmap if it is grayscale.
+    ///
+    /// Read more in [`CzFile::grayscale_ramp()`]
+    pub fn to_luma8(&self) -> Option<Vec<u8>> {
+        let ramp = self.grayscale_ramp()?;
+
+        Some(ramp.rgba_to_luma(&self.bitmap))
+    }
+
+    /// Replace the bitmap of a grayscale image with single channel L8 bytes,
+    /// keeping the existing palette. The bytes must match the size in the
+    /// header.
+    pub fn set_luma8(&mut self, luma: &[u8]) -> Result<(), CzError> {
+        let Some(ramp) = self.grayscale_ramp() else {
+            return Err(CzError::PaletteError);
+        };
+
+        if luma.len() != self.header_common.width() as usize * self.header_common.height() as usize
+        {
+            return Err(CzError::BitmapFormat);
+        }
+
+        self.bitmap = ramp.luma_to_rgba(luma);
+        self.indices = Some(luma.to_vec());
+
+        Ok(())
     }
 
     /// Returns a reference to the [`CommonHeader`] of the image.
@@ -276,7 +621,7 @@ impl CzFile {
     }
 
     pub fn set_header(&mut self, header: &CommonHeader) {
-        header.clone_into(&mut self.header_common)
+        self.header_common = *header
     }
 
     /// Returns the underlying raw buffer.
@@ -288,7 +633,50 @@ impl CzFile {
         self.bitmap
     }
 
+    /// Replace the RGBA bitmap. Any palette indices are discarded, so the
+    /// bitmap is mapped onto the palette again on save.
     pub fn set_bitmap(&mut self, bitmap: Vec<u8>) {
-        self.bitmap = bitmap
+        self.bitmap = bitmap;
+        self.indices = None;
+    }
+}
+
+/// The smallest verified encoding of an image, found by [`CzFile::optimize()`]
+#[derive(Debug, Clone)]
+pub struct Optimized {
+    /// The image converted to the chosen version and bit depth
+    pub cz: CzFile,
+
+    /// The encoded CZ file
+    pub bytes: Vec<u8>,
+}
+
+/// Information which is lost when converting a [`CzFile`] to another version
+/// or bit depth with [`CzFile::convert()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionLoss {
+    /// CZ2 files cannot store an extended header
+    ExtendedHeader,
+
+    /// The new bit depth does not use a palette
+    Palette,
+
+    /// The image had no palette, so one is generated by quantizing it
+    Quantized,
+
+    /// The new bit depth has no alpha channel, but the image is not opaque
+    Alpha,
+}
+
+impl Display for ConversionLoss {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let out_string = match self {
+            Self::ExtendedHeader => "CZ2 files cannot store an extended header, it was removed",
+            Self::Palette => "The new bit depth does not use a palette, it was removed",
+            Self::Quantized => "The image has no palette, a new one will be generated",
+            Self::Alpha => "The new bit depth has no alpha channel, transparency will be lost",
+        };
+
+        write!(f, "{}", out_string)
     }
 }
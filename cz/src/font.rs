@@ -0,0 +1,345 @@
+//! Fonts made of a CZ# atlas of glyphs and a separate font info file.
+//!
+//! The atlas is a grid of square cells, `block_size` pixels wide, with glyph
+//! `i` in cell `i % columns, i / columns`. The font info file describes the
+//! glyphs, and is laid out as follows, with all values little endian:
+//!
+//! | Type                    | Description                                   |
+//! |-------------------------|-----------------------------------------------|
+//! | `u16`                   | Font size                                     |
+//! | `u16`                   | Size of a glyph cell in the atlas             |
+//! | `u16`                   | Number of glyphs                              |
+//! | `u16`                   | Second glyph count, usually the same          |
+//! | `[u8; 3]` × glyphs      | Glyph metrics: X offset, width and Y offset   |
+//! | `u16` × 65536           | Glyph index of every UTF-16 code unit         |
+//!
+//! Any data after the glyph index table is kept as-is.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use byteorder::LE;
+
+use crate::{
+    common::CzError,
+    dynamic::CzFile,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+};
+
+/// Number of entries in the table which maps characters to glyphs
+const CHAR_MAP_LEN: usize = 0x10000;
+
+/// How a glyph is placed when it is drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlyphMetrics {
+    /// Horizontal offset of the glyph within its cell
+    pub x: u8,
+
+    /// Width the glyph takes up when drawn
+    pub width: u8,
+
+    /// Vertical offset of the glyph within its cell
+    pub y: u8,
+}
+
+/// The contents of a font info file
+#[derive(Debug, Clone)]
+pub struct FontInfo {
+    font_size: u16,
+    block_size: u16,
+    glyph_count_2: u16,
+    metrics: Vec<GlyphMetrics>,
+
+    /// Glyph index of every UTF-16 code unit, unmapped characters use glyph 0
+    char_map: Vec<u16>,
+
+    /// Anything following the character map
+    trailing: Vec<u8>,
+}
+
+impl FontInfo {
+    /// Read a font info file from anything that implements [`Read`]
+    pub fn read<T: Read>(input: &mut T) -> Result<Self, CzError> {
+        let font_size = input.read_u16::<LE>()?;
+        let block_size = input.read_u16::<LE>()?;
+        let glyph_count = input.read_u16::<LE>()?;
+        let glyph_count_2 = input.read_u16::<LE>()?;
+
+        if block_size == 0 {
+            return Err(CzError::Corrupt(String::from("Font glyph size is 0")));
+        }
+
+        let mut metrics = Vec::with_capacity(glyph_count as usize);
+        for _ in 0..glyph_count {
+            let mut bytes = [0u8; 3];
+            input.read_exact(&mut bytes)?;
+
+            metrics.push(GlyphMetrics {
+                x: bytes[0],
+                width: bytes[1],
+                y: bytes[2],
+            });
+        }
+
+        let mut char_map = vec![0u16; CHAR_MAP_LEN];
+        input.read_u16_into::<LE>(&mut char_map)?;
+
+        if let Some(index) = char_map.iter().find(|i| **i >= glyph_count) {
+            return Err(CzError::Corrupt(format!(
+                "Font character map refers to glyph {}, but there are only {}",
+                index, glyph_count
+            )));
+        }
+
+        let mut trailing = Vec::new();
+        input.read_to_end(&mut trailing)?;
+
+        Ok(Self {
+            font_size,
+            block_size,
+            glyph_count_2,
+            metrics,
+            char_map,
+            trailing,
+        })
+    }
+
+    /// Write the font info file to anything that implements [`Write`]
+    pub fn write_into<T: Write>(&self, output: &mut T) -> Result<(), CzError> {
+        output.write_u16::<LE>(self.font_size)?;
+        output.write_u16::<LE>(self.block_size)?;
+        output.write_u16::<LE>(self.metrics.len() as u16)?;
+        output.write_u16::<LE>(self.glyph_count_2)?;
+
+        for m in &self.metrics {
+            output.write_all(&[m.x, m.width, m.y])?;
+        }
+
+        for index in &self.char_map {
+            output.write_u16::<LE>(*index)?;
+        }
+
+        output.write_all(&self.trailing)?;
+
+        Ok(())
+    }
+
+    pub fn font_size(&self) -> u16 {
+        self.font_size
+    }
+
+    /// Width and height of a glyph cell in the atlas
+    pub fn block_size(&self) -> u16 {
+        self.block_size
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.metrics.len()
+    }
+
+    pub fn metrics(&self) -> &[GlyphMetrics] {
+        &self.metrics
+    }
+
+    /// Get the glyph index used to draw a character. Characters outside of
+    /// the Basic Multilingual Plane cannot be drawn.
+    pub fn glyph_index(&self, c: char) -> Option<u16> {
+        let code = u16::try_from(c as u32).ok()?;
+
+        Some(self.char_map[code as usize])
+    }
+
+    /// Second glyph count from the header, which is usually the same as
+    /// [`FontInfo::glyph_count()`]
+    pub fn glyph_count_2(&self) -> u16 {
+        self.glyph_count_2
+    }
+
+    /// List the UTF-16 code units which are drawn with each glyph. Glyph 0 is
+    /// also used for every code unit which is not listed.
+    fn glyph_codes(&self) -> Vec<Vec<u16>> {
+        let mut codes = vec![Vec::new(); self.metrics.len()];
+        for (code, index) in self.char_map.iter().enumerate() {
+            if *index != 0 {
+                codes[*index as usize].push(code as u16);
+            }
+        }
+
+        codes
+    }
+}
+
+/// A single glyph cut out of a font atlas
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    /// UTF-16 code units which are drawn with this glyph. These can include
+    /// surrogates, so they are not stored as characters.
+    pub codes: Vec<u16>,
+
+    pub metrics: GlyphMetrics,
+
+    /// RGBA bitmap of the glyph's cell, which is `block_size` pixels square
+    pub bitmap: Vec<u8>,
+}
+
+impl Glyph {
+    /// Characters which are drawn with this glyph, leaving out surrogates
+    pub fn chars(&self) -> Vec<char> {
+        self.codes
+            .iter()
+            .filter_map(|c| char::from_u32(*c as u32))
+            .collect()
+    }
+}
+
+/// A font split into separate glyphs, which can be edited and built back into
+/// an atlas and font info file
+#[derive(Debug, Clone)]
+pub struct Font {
+    info: FontInfo,
+
+    /// The original atlas, without its bitmap
+    atlas: CzFile,
+    columns: u16,
+
+    glyphs: Vec<Glyph>,
+}
+
+impl Font {
+    /// Cut a font atlas into glyphs using its font info
+    pub fn from_atlas(info: FontInfo, atlas: &CzFile) -> Result<Self, CzError> {
+        let block_size = info.block_size as usize;
+        let width = atlas.header().width() as usize;
+        let height = atlas.header().height() as usize;
+
+        let columns = width / block_size;
+        let rows = height / block_size;
+        if columns * rows < info.glyph_count() {
+            return Err(CzError::Corrupt(format!(
+                "Font atlas has room for {} glyphs, but there are {}",
+                columns * rows,
+                info.glyph_count()
+            )));
+        }
+
+        let glyphs = info
+            .glyph_codes()
+            .into_iter()
+            .zip(info.metrics.iter())
+            .enumerate()
+            .map(|(i, (codes, metrics))| {
+                let (cell_x, cell_y) = ((i % columns) * block_size, (i / columns) * block_size);
+
+                let mut bitmap = Vec::with_capacity(block_size * block_size * 4);
+                for y in cell_y..cell_y + block_size {
+                    let start = (y * width + cell_x) * 4;
+                    bitmap.extend_from_slice(&atlas.as_raw()[start..start + block_size * 4]);
+                }
+
+                Glyph {
+                    codes,
+                    metrics: *metrics,
+                    bitmap,
+                }
+            })
+            .collect();
+
+        let mut atlas = atlas.clone();
+        atlas.set_bitmap(Vec::new());
+
+        Ok(Self {
+            info,
+            atlas,
+            columns: columns as u16,
+            glyphs,
+        })
+    }
+
+    pub fn info(&self) -> &FontInfo {
+        &self.info
+    }
+
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
+
+    /// Returns a mutable reference to the glyphs, which can be edited, added
+    /// or removed before building the font again.
+    pub fn glyphs_mut(&mut self) -> &mut Vec<Glyph> {
+        &mut self.glyphs
+    }
+
+    /// Get the glyph used to draw a character
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(self.info.glyph_index(c)? as usize)
+    }
+
+    /// Build the glyphs into a new atlas and font info file. The atlas keeps
+    /// the version, bit depth and width of the original one, and grows or
+    /// shrinks in height to fit the glyphs. The second glyph count
+    /// follows the number of glyphs if it matched it originally.
+    pub fn build(&self) -> Result<(FontInfo, CzFile), CzError> {
+        let block_size = self.info.block_size as usize;
+        let columns = self.columns as usize;
+
+        if self.glyphs.is_empty() || self.glyphs.len() > u16::MAX as usize {
+            return Err(CzError::Corrupt(format!(
+                "A font must have between 1 and {} glyphs",
+                u16::MAX
+            )));
+        }
+
+        let mut char_map = vec![0u16; CHAR_MAP_LEN];
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            if glyph.bitmap.len() != block_size * block_size * 4 {
+                return Err(CzError::BitmapFormat);
+            }
+
+            for code in &glyph.codes {
+                char_map[*code as usize] = i as u16;
+            }
+        }
+
+        // Any space to the right of the last column is left empty
+        let width = self.atlas.header().width() as usize;
+        let rows = self.glyphs.len().div_ceil(columns);
+        let height = rows * block_size;
+        if height > u16::MAX as usize {
+            return Err(CzError::Corrupt(String::from(
+                "Font atlas is too tall for the number of glyphs",
+            )));
+        }
+
+        let mut bitmap = vec![0u8; width * height * 4];
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            let (cell_x, cell_y) = ((i % columns) * block_size, (i / columns) * block_size);
+
+            for (row, line) in glyph.bitmap.chunks_exact(block_size * 4).enumerate() {
+                let start = ((cell_y + row) * width + cell_x) * 4;
+                bitmap[start..start + block_size * 4].copy_from_slice(line);
+            }
+        }
+
+        let mut atlas = self.atlas.clone();
+        atlas.header_mut().set_height(height as u16);
+
+        // Keep the original palette if it still has every color
+        let palette = atlas.palette().clone().filter(|p| p.covers(&bitmap));
+        atlas.set_bitmap(bitmap);
+        *atlas.palette_mut() = palette;
+
+        let glyph_count_2 = if self.info.glyph_count_2 as usize == self.info.glyph_count() {
+            self.glyphs.len() as u16
+        } else {
+            self.info.glyph_count_2
+        };
+
+        let info = FontInfo {
+            glyph_count_2,
+            metrics: self.glyphs.iter().map(|g| g.metrics).collect(),
+            char_map,
+            ..self.info.clone()
+        };
+
+        Ok((info, atlas))
+    }
+}
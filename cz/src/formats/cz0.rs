@@ -1,6 +1,7 @@
-use std::io::{Read, Seek, Write};
+use alloc::{vec, vec::Vec};
 
 use crate::common::CzError;
+use crate::io::{Read, Seek, Write};
 
 pub fn decode<T: Seek + Read>(input: &mut T) -> Result<Vec<u8>, CzError> {
     // Get the rest of the file, which is the bitmap
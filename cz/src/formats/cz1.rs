@@ -1,7 +1,8 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use alloc::vec::Vec;
 
 use crate::common::CzError;
 use crate::compression::{compress, decompress, get_chunk_info};
+use crate::io::{Read, Seek, SeekFrom, Write};
 
 pub fn decode<T: Seek + Read>(bytes: &mut T) -> Result<Vec<u8>, CzError> {
     // Get information about the compressed chunks
@@ -9,13 +10,13 @@ pub fn decode<T: Seek + Read>(bytes: &mut T) -> Result<Vec<u8>, CzError> {
     bytes.seek(SeekFrom::Start(block_info.length as u64))?;
 
     // Get the bitmap
-    let bitmap = decompress(bytes, &block_info).unwrap();
+    let bitmap = decompress(bytes, &block_info)?;
 
     Ok(bitmap)
 }
 
 pub fn encode<T: Write>(output: &mut T, bitmap: &[u8]) -> Result<(), CzError> {
-    let (compressed_data, compressed_info) = compress(bitmap, 0xFEFD);
+    let (compressed_data, compressed_info) = compress(bitmap, 0xFEFD)?;
 
     compressed_info.write_into(output)?;
 
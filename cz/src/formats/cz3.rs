@@ -1,7 +1,8 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use alloc::{string::String, vec::Vec};
 
 use crate::common::{CommonHeader, CzError};
 use crate::compression::{compress, decompress, get_chunk_info};
+use crate::io::{Read, Seek, SeekFrom, Write};
 
 pub fn decode<T: Seek + Read>(bytes: &mut T, header: &CommonHeader) -> Result<Vec<u8>, CzError> {
     let block_info = get_chunk_info(bytes)?;
@@ -9,7 +10,7 @@ pub fn decode<T: Seek + Read>(bytes: &mut T, header: &CommonHeader) -> Result<Ve
 
     let data = decompress(bytes, &block_info)?;
 
-    let bitmap = line_diff(header, &data);
+    let bitmap = line_diff(header, &data)?;
 
     Ok(bitmap)
 }
@@ -21,7 +22,7 @@ pub fn encode<T: Write>(
 ) -> Result<(), CzError> {
     let bitmap = diff_line(header, bitmap);
 
-    let (compressed_data, compressed_info) = compress(&bitmap, 0xFEFD);
+    let (compressed_data, compressed_info) = compress(&bitmap, 0xFEFD)?;
 
     compressed_info.write_into(output)?;
 
@@ -34,15 +35,19 @@ pub fn encode<T: Write>(
 ///
 /// Uses the previous line to determine the characterisitcs of the
 /// following lines
-fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
+fn line_diff(header: &CommonHeader, data: &[u8]) -> Result<Vec<u8>, CzError> {
     let width = header.width() as u32;
     let height = header.height() as u32;
     let mut output_buf = data.to_vec();
 
-    let block_height = (f32::ceil(height as f32 / 3.0) as u16) as usize;
+    let block_height = height.div_ceil(3) as usize;
     let pixel_byte_count = header.depth() >> 3;
     let line_byte_count = (width * pixel_byte_count as u32) as usize;
 
+    if data.len() < line_byte_count * height as usize {
+        return Err(CzError::Corrupt(String::from("Bitmap size incorrect")));
+    }
+
     let mut curr_line;
     let mut prev_line = Vec::with_capacity(line_byte_count);
 
@@ -62,7 +67,7 @@ fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
             output_buf[index..index + line_byte_count].copy_from_slice(&curr_line);
         } else if pixel_byte_count == 3 {
             for x in (0..line_byte_count).step_by(3) {
-                let loc = (y * 3 * width) as usize + x;
+                let loc = y as usize * 3 * width as usize + x;
 
                 output_buf[loc..loc + 3].copy_from_slice(&[
                     curr_line[x],
@@ -72,7 +77,7 @@ fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
             }
         } else if pixel_byte_count == 1 {
             for (x, rgba) in curr_line.iter().enumerate().take(line_byte_count) {
-                let loc = (y * width) as usize + x;
+                let loc = y as usize * width as usize + x;
 
                 output_buf[loc] = *rgba;
             }
@@ -81,7 +86,7 @@ fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
         index += line_byte_count;
     }
 
-    output_buf
+    Ok(output_buf)
 }
 
 /// Function to encode data into the CZ3 format before compression
@@ -93,7 +98,7 @@ fn diff_line(header: &CommonHeader, input: &[u8]) -> Vec<u8> {
 
     let mut data = Vec::with_capacity(input.len());
 
-    let block_height = (f32::ceil(height as f32 / 3.0) as u16) as usize;
+    let block_height = height.div_ceil(3) as usize;
     let pixel_byte_count = header.depth() >> 3;
     let line_byte_count = (width * pixel_byte_count as u32) as usize;
 
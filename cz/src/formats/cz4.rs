@@ -1,7 +1,9 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use alloc::{string::String, vec::Vec};
 
+use crate::color::split_alpha;
 use crate::common::{CommonHeader, CzError};
 use crate::compression::{compress, decompress, get_chunk_info};
+use crate::io::{Read, Seek, SeekFrom, Write};
 
 pub fn decode<T: Seek + Read>(bytes: &mut T, header: &CommonHeader) -> Result<Vec<u8>, CzError> {
     let block_info = get_chunk_info(bytes)?;
@@ -9,7 +11,7 @@ pub fn decode<T: Seek + Read>(bytes: &mut T, header: &CommonHeader) -> Result<Ve
 
     let data = decompress(bytes, &block_info)?;
 
-    let bitmap = line_diff(header, &data);
+    let bitmap = line_diff(header, &data)?;
 
     Ok(bitmap)
 }
@@ -21,7 +23,7 @@ pub fn encode<T: Write>(
 ) -> Result<(), CzError> {
     let bitmap = diff_line(header, bitmap);
 
-    let (compressed_data, compressed_info) = compress(&bitmap, 0xFEFD);
+    let (compressed_data, compressed_info) = compress(&bitmap, 0xFEFD)?;
 
     compressed_info.write_into(output)?;
 
@@ -30,13 +32,18 @@ pub fn encode<T: Write>(
     Ok(())
 }
 
-fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
+fn line_diff(header: &CommonHeader, data: &[u8]) -> Result<Vec<u8>, CzError> {
     let width = header.width() as u32;
     let height = header.height() as u32;
 
-    let mut output_buf = Vec::with_capacity((width * height * 4) as usize);
+    let pixel_count = width as usize * height as usize;
+    if data.len() < pixel_count * 4 {
+        return Err(CzError::Corrupt(String::from("Bitmap size incorrect")));
+    }
+
+    let mut output_buf = Vec::with_capacity(pixel_count * 4);
 
-    let block_height = (f32::ceil(height as f32 / 3.0) as u16) as u32;
+    let block_height = height.div_ceil(3);
 
     let mut curr_line;
     let mut prev_line = Vec::with_capacity(width as usize * 3);
@@ -45,7 +52,7 @@ fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
     let mut prev_alpha = Vec::with_capacity(width as usize);
 
     let mut rgb_index = 0;
-    let mut alpha_index = (width * height * 3) as usize;
+    let mut alpha_index = pixel_count * 3;
     for y in 0..height {
         curr_line = data[rgb_index..rgb_index + width as usize * 3].to_vec();
         curr_alpha = data[alpha_index..alpha_index + width as usize].to_vec();
@@ -81,7 +88,7 @@ fn line_diff(header: &CommonHeader, data: &[u8]) -> Vec<u8> {
         alpha_index += width as usize;
     }
 
-    output_buf
+    Ok(output_buf)
 }
 
 fn diff_line(header: &CommonHeader, input: &[u8]) -> Vec<u8> {
@@ -91,7 +98,7 @@ fn diff_line(header: &CommonHeader, input: &[u8]) -> Vec<u8> {
     let mut data = Vec::with_capacity(width as usize * 3);
     let mut alpha_data = Vec::with_capacity(width as usize);
 
-    let block_height = (f32::ceil(height as f32 / 3.0) as u16) as usize;
+    let block_height = height.div_ceil(3) as usize;
     let pixel_byte_count = header.depth() >> 3;
     let line_byte_count = (width * pixel_byte_count as u32) as usize;
 
@@ -103,18 +110,7 @@ fn diff_line(header: &CommonHeader, input: &[u8]) -> Vec<u8> {
 
     let mut i = 0;
     for y in 0..height {
-        curr_line = input[i..i + line_byte_count]
-            .windows(4)
-            .step_by(4)
-            .flat_map(|r| &r[0..3])
-            .copied()
-            .collect();
-        curr_alpha = input[i..i + line_byte_count]
-            .iter()
-            .skip(3)
-            .step_by(4)
-            .copied()
-            .collect();
+        (curr_line, curr_alpha) = split_alpha(&input[i..i + line_byte_count]);
 
         if y % block_height as u32 != 0 {
             for x in 0..width as usize * 3 {
@@ -0,0 +1,190 @@
+//! Information about CZ# files which can be read without decoding the image
+
+use alloc::{string::String, vec};
+#[cfg(feature = "std")]
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::{
+    color::{Palette, get_palette},
+    common::{CommonHeader, CzError, CzVersion, ExtendedHeader},
+    compression::get_chunk_info,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+pub use crate::compression::{ChunkInfo, CompressionInfo};
+
+/// The headers, palette and compression chunk information of a CZ# file
+#[derive(Debug, Clone)]
+pub struct CzInfo {
+    header_common: CommonHeader,
+    header_extended: Option<ExtendedHeader>,
+    palette: Option<Palette>,
+
+    /// Information about the compressed chunks, if the version is compressed
+    compression: Option<CompressionInfo>,
+
+    /// Offset of the image data, directly after the headers and palette
+    data_offset: u64,
+}
+
+impl CzInfo {
+    /// Open a CZ# file from a path and read its information
+    #[cfg(feature = "std")]
+    pub fn open<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, CzError> {
+        let mut img_file = BufReader::new(File::open(path)?);
+
+        Self::read(&mut img_file)
+    }
+
+    /// Read the information of a CZ# file from anything that implements
+    /// [`Read`] and [`Seek`], without decoding the image data
+    pub fn read<T: Seek + Read>(input: &mut T) -> Result<Self, CzError> {
+        let (header_common, header_extended, palette) = read_headers(input)?;
+        let data_offset = input.stream_position()?;
+
+        let compression = match header_common.version() {
+            CzVersion::CZ1 | CzVersion::CZ2 | CzVersion::CZ3 | CzVersion::CZ4 => {
+                Some(get_chunk_info(input)?)
+            }
+            CzVersion::CZ0 | CzVersion::CZ5 => None,
+        };
+
+        Ok(Self {
+            header_common,
+            header_extended,
+            palette,
+            compression,
+            data_offset,
+        })
+    }
+
+    /// Returns a reference to the [`CommonHeader`] of the image.
+    pub fn header(&self) -> &CommonHeader {
+        &self.header_common
+    }
+
+    pub fn extended_header(&self) -> &Option<ExtendedHeader> {
+        &self.header_extended
+    }
+
+    /// Returns a reference to the palette if it exists.
+    pub fn palette(&self) -> &Option<Palette> {
+        &self.palette
+    }
+
+    /// Returns the compression chunk information, if the image is compressed.
+    pub fn compression(&self) -> &Option<CompressionInfo> {
+        &self.compression
+    }
+
+    /// Offset of the image data within the file, which begins with the
+    /// compression chunk information for compressed versions.
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+}
+
+/// Rewrite the headers of a CZ# file without decoding it, copying the
+/// palette and compressed image data through unchanged.
+///
+/// The headers read from the input are passed to `edit` to be modified. Only
+/// fields which do not affect the image data can be changed, so changing the
+/// version, width, height or depth returns [`CzError::HeaderEdit`]. The header
+/// length is updated to fit the extended header.
+pub fn edit_header<I, O, F>(input: &mut I, output: &mut O, edit: F) -> Result<(), CzError>
+where
+    I: Seek + Read,
+    O: Write,
+    F: FnOnce(&mut CommonHeader, &mut Option<ExtendedHeader>),
+{
+    let (original_common, original_extended, _) = read_headers(input)?;
+
+    // Keep the raw header bytes, so anything not represented by the header
+    // structs is kept as it was
+    let original_length = original_common.length().max(15);
+    let mut raw_header = vec![0u8; original_length];
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut raw_header)?;
+
+    let mut common = original_common;
+    let mut extended = original_extended;
+    edit(&mut common, &mut extended);
+
+    if common.version() != original_common.version()
+        || common.width() != original_common.width()
+        || common.height() != original_common.height()
+        || common.depth() != original_common.depth()
+    {
+        return Err(CzError::HeaderEdit(String::from(
+            "the version, width, height and depth must stay the same",
+        )));
+    }
+
+    if common.version() == CzVersion::CZ2 && extended.is_some() {
+        return Err(CzError::HeaderEdit(String::from(
+            "CZ2 files cannot have an extended header",
+        )));
+    }
+
+    // The length stays the same unless the layout of the extended header
+    // changes, in which case it fits the new layout
+    let layout = |e: &Option<ExtendedHeader>| e.map(|e| e.unknown_width.is_some());
+    let structured_length = match layout(&extended) {
+        None => 15,
+        Some(false) => 28,
+        Some(true) => 36,
+    };
+
+    let length = if common.version() == CzVersion::CZ2 {
+        original_length
+    } else if layout(&extended) == layout(&original_extended) {
+        original_length.max(structured_length)
+    } else {
+        structured_length
+    };
+
+    let mut header = raw_header[..15].to_vec();
+    header[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+    header[14] = common.color_block();
+
+    if let Some(ext) = extended {
+        ext.write_into(&mut header)?;
+    }
+
+    // Fill out the rest of the header from the original
+    while header.len() < length {
+        header.push(raw_header.get(header.len()).copied().unwrap_or_default());
+    }
+    header.truncate(length);
+
+    output.write_all(&header)?;
+
+    input.seek(SeekFrom::Start(original_length as u64))?;
+    io::copy(input, output)?;
+
+    Ok(())
+}
+
+/// Read the headers and palette of a CZ# file, leaving the input positioned
+/// at the start of the image data
+pub(crate) fn read_headers<T: Seek + Read>(
+    input: &mut T,
+) -> Result<(CommonHeader, Option<ExtendedHeader>, Option<Palette>), CzError> {
+    // Get the header common to all CZ images
+    let header_common = CommonHeader::from_bytes(input)?;
+    let mut header_extended = None;
+    if header_common.length() > 15 && header_common.version() != CzVersion::CZ2 {
+        header_extended = Some(ExtendedHeader::from_bytes(input, &header_common)?);
+    }
+    input.seek(SeekFrom::Start(header_common.length() as u64))?;
+
+    // Get the color palette if the bit depth is 8 or less
+    let palette = if header_common.depth() <= 8 {
+        let color_count = 1 << header_common.depth();
+        Some(get_palette(input, color_count)?)
+    } else {
+        None
+    };
+
+    Ok((header_common, header_extended, palette))
+}
@@ -0,0 +1,351 @@
+//! Traits for reading and writing CZ# files.
+//!
+//! With the `std` feature these are the [`std::io`] traits, along with the
+//! byte order extensions from [`byteorder`]. Without it this module provides
+//! the small part of them which the decoders and encoders use, with a
+//! [`Cursor`] for reading and writing byte slices and vectors.
+
+#[cfg(feature = "std")]
+pub use byteorder::{ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+pub use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write, copy};
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std::*;
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use alloc::vec::Vec;
+    use byteorder::ByteOrder;
+    use core::fmt;
+
+    /// The kind of an [`Error`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        /// The input ended before all of the data could be read
+        UnexpectedEof,
+
+        /// A seek went before the start of the data
+        InvalidInput,
+
+        /// The output could not hold all of the data
+        WriteZero,
+    }
+
+    /// An error while reading or writing
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+    }
+
+    impl Error {
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Self { kind }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let message = match self.kind {
+                ErrorKind::UnexpectedEof => "unexpected end of data",
+                ErrorKind::InvalidInput => "invalid seek to a negative position",
+                ErrorKind::WriteZero => "failed to write the whole buffer",
+            };
+
+            f.write_str(message)
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// A position to seek to, the same as `std::io::SeekFrom`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SeekFrom {
+        Start(u64),
+        End(i64),
+        Current(i64),
+    }
+
+    pub trait Read {
+        /// Read some bytes into a buffer, and return how many were read.
+        /// Returns 0 at the end of the data.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf)? {
+                    0 => return Err(ErrorKind::UnexpectedEof.into()),
+                    n => buf = &mut buf[n..],
+                }
+            }
+
+            Ok(())
+        }
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let start = buf.len();
+            let mut chunk = [0u8; 4096];
+            loop {
+                match self.read(&mut chunk)? {
+                    0 => return Ok(buf.len() - start),
+                    n => buf.extend_from_slice(&chunk[..n]),
+                }
+            }
+        }
+    }
+
+    pub trait Write {
+        /// Write some bytes from a buffer, and return how many were written
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf)? {
+                    0 => return Err(ErrorKind::WriteZero.into()),
+                    n => buf = &buf[n..],
+                }
+            }
+
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Copy everything left in a reader into a writer, and return how many
+    /// bytes were copied
+    pub fn copy<R, W>(reader: &mut R, writer: &mut W) -> Result<u64>
+    where
+        R: Read + ?Sized,
+        W: Write + ?Sized,
+    {
+        let mut chunk = [0u8; 4096];
+        let mut copied = 0;
+        loop {
+            match reader.read(&mut chunk)? {
+                0 => return Ok(copied),
+                n => {
+                    writer.write_all(&chunk[..n])?;
+                    copied += n as u64;
+                }
+            }
+        }
+    }
+
+    pub trait Seek {
+        /// Seek to a position, and return the new position from the start
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+        fn stream_position(&mut self) -> Result<u64> {
+            self.seek(SeekFrom::Current(0))
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+    }
+
+    impl<S: Seek + ?Sized> Seek for &mut S {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            (**self).seek(pos)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let len = buf.len().min(self.len());
+            let (data, rest) = self.split_at(len);
+            buf[..len].copy_from_slice(data);
+            *self = rest;
+
+            Ok(len)
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+    }
+
+    /// Reads and writes bytes in memory at a position which can be moved,
+    /// the same as `std::io::Cursor`
+    #[derive(Debug, Clone, Default)]
+    pub struct Cursor<T> {
+        inner: T,
+        pos: u64,
+    }
+
+    impl<T> Cursor<T> {
+        pub fn new(inner: T) -> Self {
+            Self { inner, pos: 0 }
+        }
+
+        pub fn into_inner(self) -> T {
+            self.inner
+        }
+
+        pub fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            &mut self.inner
+        }
+
+        pub fn position(&self) -> u64 {
+            self.pos
+        }
+
+        pub fn set_position(&mut self, pos: u64) {
+            self.pos = pos
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Read for Cursor<T> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let data = self.inner.as_ref();
+            let start = (self.pos as usize).min(data.len());
+            let len = (&data[start..]).read(buf)?;
+            self.pos += len as u64;
+
+            Ok(len)
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            let (base, offset) = match pos {
+                SeekFrom::Start(n) => {
+                    self.pos = n;
+                    return Ok(n);
+                }
+                SeekFrom::End(n) => (self.inner.as_ref().len() as u64, n),
+                SeekFrom::Current(n) => (self.pos, n),
+            };
+
+            match base.checked_add_signed(offset) {
+                Some(n) => {
+                    self.pos = n;
+                    Ok(n)
+                }
+                None => Err(ErrorKind::InvalidInput.into()),
+            }
+        }
+    }
+
+    /// Write into a vector at a position, filling any gap with zeros
+    fn write_into_vec(vec: &mut Vec<u8>, pos: &mut u64, buf: &[u8]) -> usize {
+        let start = *pos as usize;
+        let end = start + buf.len();
+        if vec.len() < end {
+            vec.resize(end, 0);
+        }
+        vec[start..end].copy_from_slice(buf);
+        *pos = end as u64;
+
+        buf.len()
+    }
+
+    impl Write for Cursor<Vec<u8>> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            Ok(write_into_vec(&mut self.inner, &mut self.pos, buf))
+        }
+    }
+
+    impl Write for Cursor<&mut Vec<u8>> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            Ok(write_into_vec(self.inner, &mut self.pos, buf))
+        }
+    }
+
+    impl Write for Cursor<&mut [u8]> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let start = (self.pos as usize).min(self.inner.len());
+            let len = buf.len().min(self.inner.len() - start);
+            self.inner[start..start + len].copy_from_slice(&buf[..len]);
+            self.pos += len as u64;
+
+            Ok(len)
+        }
+    }
+
+    /// Read numbers in a chosen byte order, the same as
+    /// `byteorder::ReadBytesExt`
+    pub trait ReadBytesExt: Read {
+        fn read_u8(&mut self) -> Result<u8> {
+            let mut buf = [0; 1];
+            self.read_exact(&mut buf)?;
+
+            Ok(buf[0])
+        }
+
+        fn read_u16<B: ByteOrder>(&mut self) -> Result<u16> {
+            let mut buf = [0; 2];
+            self.read_exact(&mut buf)?;
+
+            Ok(B::read_u16(&buf))
+        }
+
+        fn read_u32<B: ByteOrder>(&mut self) -> Result<u32> {
+            let mut buf = [0; 4];
+            self.read_exact(&mut buf)?;
+
+            Ok(B::read_u32(&buf))
+        }
+
+        fn read_u16_into<B: ByteOrder>(&mut self, dst: &mut [u16]) -> Result<()> {
+            for value in dst {
+                *value = self.read_u16::<B>()?;
+            }
+
+            Ok(())
+        }
+    }
+
+    impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+    /// Write numbers in a chosen byte order, the same as
+    /// `byteorder::WriteBytesExt`
+    pub trait WriteBytesExt: Write {
+        fn write_u8(&mut self, n: u8) -> Result<()> {
+            self.write_all(&[n])
+        }
+
+        fn write_u16<B: ByteOrder>(&mut self, n: u16) -> Result<()> {
+            let mut buf = [0; 2];
+            B::write_u16(&mut buf, n);
+
+            self.write_all(&buf)
+        }
+
+        fn write_u32<B: ByteOrder>(&mut self, n: u32) -> Result<()> {
+            let mut buf = [0; 4];
+            B::write_u32(&mut buf, n);
+
+            self.write_all(&buf)
+        }
+    }
+
+    impl<W: Write + ?Sized> WriteBytesExt for W {}
+}
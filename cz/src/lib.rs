@@ -1,9 +1,18 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 mod binio;
 mod color;
 mod compression;
 
 pub mod common;
+pub mod diff;
 pub mod dynamic;
+pub mod font;
+pub mod info;
+pub mod io;
+pub mod resize;
 
 mod formats {
     pub(crate) mod cz0;
@@ -13,10 +22,13 @@ mod formats {
     pub(crate) mod cz4;
 }
 
+#[cfg(feature = "std")]
 use common::CzError;
+#[cfg(feature = "std")]
 use std::{io::BufReader, path::Path};
 
 /// Open a CZ# file from a path
+#[cfg(feature = "std")]
 pub fn open<P: ?Sized + AsRef<Path>>(path: &P) -> Result<CzFile, CzError> {
     let mut img_file = BufReader::new(std::fs::File::open(path)?);
 
@@ -26,6 +38,14 @@ pub fn open<P: ?Sized + AsRef<Path>>(path: &P) -> Result<CzFile, CzError> {
 #[doc(inline)]
 pub use dynamic::CzFile;
 
+#[doc(inline)]
+pub use color::{GrayscaleRamp, Palette, SWATCH_WIDTH, choose_depth, join_alpha, split_alpha};
+
+#[doc(inline)]
+pub use resize::ResizeFilter;
+
+pub use rgb::RGBA8;
+
 /*
 #[doc(inline)]
 pub use formats::cz0::Cz0Image;
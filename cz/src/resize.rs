@@ -0,0 +1,181 @@
+//! Resampling of RGBA bitmaps
+
+use alloc::{vec, vec::Vec};
+
+use crate::common::CzError;
+
+/// Filter used to resample an image when resizing it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// Use the closest pixel, which keeps the exact colors of the image
+    Nearest,
+
+    /// Linear interpolation between the surrounding pixels
+    Bilinear,
+
+    /// Catmull-Rom cubic interpolation, which is sharper than bilinear
+    #[default]
+    Bicubic,
+}
+
+impl ResizeFilter {
+    /// Distance from the center of a sample beyond which the filter is zero
+    fn support(&self) -> f32 {
+        match self {
+            ResizeFilter::Nearest => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Bicubic => 2.0,
+        }
+    }
+
+    fn kernel(&self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            ResizeFilter::Nearest => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+            ResizeFilter::Bicubic => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// The source samples and their weights for each pixel of a resampled line
+struct Weights {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn line_weights(src_len: usize, dst_len: usize, filter: ResizeFilter) -> Vec<Weights> {
+    let scale = src_len as f32 / dst_len as f32;
+
+    // Widen the filter when shrinking so every source pixel contributes
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = ((center - support) as isize).max(0) as usize;
+            let end = ((center + support) as usize + 1).min(src_len);
+
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.kernel((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+
+            let total: f32 = weights.iter().sum();
+            if total != 0.0 {
+                weights.iter_mut().for_each(|w| *w /= total);
+            }
+
+            Weights { start, weights }
+        })
+        .collect()
+}
+
+/// Resize an RGBA bitmap from one width and height to another.
+///
+/// Colors are filtered with premultiplied alpha, so transparent pixels do not
+/// bleed into the edges of a sprite. The nearest filter copies pixels as-is.
+///
+/// The bitmap must hold exactly `size` pixels, and have at least one.
+pub fn resize_rgba(
+    bitmap: &[u8],
+    size: (usize, usize),
+    new_size: (usize, usize),
+    filter: ResizeFilter,
+) -> Result<Vec<u8>, CzError> {
+    let (width, height) = size;
+    let (new_width, new_height) = new_size;
+
+    if width.checked_mul(height).and_then(|n| n.checked_mul(4)) != Some(bitmap.len()) {
+        return Err(CzError::BitmapFormat);
+    } else if bitmap.is_empty() {
+        return Err(CzError::EmptyBitmap);
+    }
+
+    if filter == ResizeFilter::Nearest {
+        let mut output = Vec::with_capacity(new_width * new_height * 4);
+        for y in 0..new_height {
+            let src_y = (y * 2 + 1) * height / (new_height * 2);
+            for x in 0..new_width {
+                let src_x = (x * 2 + 1) * width / (new_width * 2);
+                let i = (src_y * width + src_x) * 4;
+                output.extend_from_slice(&bitmap[i..i + 4]);
+            }
+        }
+
+        return Ok(output);
+    }
+
+    let premultiplied: Vec<f32> = bitmap
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let a = p[3] as f32 / 255.0;
+            [
+                p[0] as f32 * a,
+                p[1] as f32 * a,
+                p[2] as f32 * a,
+                p[3] as f32,
+            ]
+        })
+        .collect();
+
+    // Resample each row, then each column of the result
+    let mut horizontal = vec![0f32; new_width * height * 4];
+    let x_weights = line_weights(width, new_width, filter);
+    for y in 0..height {
+        for (x, w) in x_weights.iter().enumerate() {
+            let out = (y * new_width + x) * 4;
+            for (k, weight) in w.weights.iter().enumerate() {
+                let i = (y * width + w.start + k) * 4;
+                for c in 0..4 {
+                    horizontal[out + c] += premultiplied[i + c] * weight;
+                }
+            }
+        }
+    }
+
+    let mut output = vec![0u8; new_width * new_height * 4];
+    let y_weights = line_weights(height, new_height, filter);
+    for (y, w) in y_weights.iter().enumerate() {
+        for x in 0..new_width {
+            let mut pixel = [0f32; 4];
+            for (k, weight) in w.weights.iter().enumerate() {
+                let i = ((w.start + k) * new_width + x) * 4;
+                for c in 0..4 {
+                    pixel[c] += horizontal[i + c] * weight;
+                }
+            }
+
+            // Divide by the alpha before clamping it, as the bicubic filter
+            // can overshoot and the colors overshoot by the same amount
+            let alpha = pixel[3];
+            let out = (y * new_width + x) * 4;
+            for c in 0..3 {
+                let color = if alpha > 0.0 {
+                    pixel[c] * 255.0 / alpha
+                } else {
+                    0.0
+                };
+                output[out + c] = (color.clamp(0.0, 255.0) + 0.5) as u8;
+            }
+            output[out + 3] = (alpha.clamp(0.0, 255.0) + 0.5) as u8;
+        }
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,41 @@
+use cz::{CzFile, choose_depth, common::CzVersion, io::Cursor};
+
+#[test]
+fn auto_depth_is_lossless() {
+    let (width, height) = (32u16, 32u16);
+    let pixel_count = width as usize * height as usize;
+
+    let opaque: Vec<u8> = (0..pixel_count * 4)
+        .map(|i| {
+            if i % 4 == 3 {
+                0xFF
+            } else {
+                (i * 7 % 256) as u8
+            }
+        })
+        .collect();
+    let few_colors: Vec<u8> = (0..pixel_count)
+        .flat_map(|i| [(i % 64) as u8, 0x40, 0x80, (i % 4) as u8 * 0x40])
+        .collect();
+    let many_colors: Vec<u8> = (0..pixel_count)
+        .flat_map(|i| [(i % 256) as u8, (i / 256) as u8, 0x20, (i % 7) as u8])
+        .collect();
+
+    assert_eq!(choose_depth(&opaque), 24);
+    assert_eq!(choose_depth(&few_colors), 8);
+    assert_eq!(choose_depth(&many_colors), 32);
+
+    for bitmap in [opaque, few_colors, many_colors] {
+        for version in [CzVersion::CZ0, CzVersion::CZ3, CzVersion::CZ4] {
+            let mut original_cz = CzFile::from_raw(version, width, height, bitmap.clone());
+            original_cz.set_auto_depth();
+
+            let mut cz_bytes = Cursor::new(Vec::new());
+            original_cz.encode(&mut cz_bytes).unwrap();
+            cz_bytes.set_position(0);
+
+            let decoded_cz = CzFile::decode(&mut cz_bytes).unwrap();
+            assert_eq!(decoded_cz.as_raw(), &bitmap);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use cz::{join_alpha, split_alpha};
+
+#[test]
+fn split_join_alpha() {
+    let rgba: Vec<u8> = (0..64 * 4).map(|i| (i * 11 % 256) as u8).collect();
+
+    let (rgb, alpha) = split_alpha(&rgba);
+    assert_eq!(rgb.len(), 64 * 3);
+    assert_eq!(
+        alpha,
+        rgba.iter().skip(3).step_by(4).copied().collect::<Vec<_>>()
+    );
+    assert_eq!(join_alpha(&rgb, &alpha).unwrap(), rgba);
+
+    assert!(join_alpha(&rgb, &alpha[1..]).is_err());
+}
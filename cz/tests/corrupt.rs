@@ -0,0 +1,85 @@
+use cz::{
+    CzFile,
+    common::{CzError, CzVersion},
+    io::Cursor,
+};
+
+/// Build a CZ file with the common header and the given data after it
+fn cz_bytes(version: u8, width: u16, height: u16, depth: u16, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![b'C', b'Z', b'0' + version, 0];
+    bytes.extend_from_slice(&15u32.to_le_bytes());
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes.extend_from_slice(&depth.to_le_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(data);
+
+    bytes
+}
+
+/// Chunk info for a single chunk followed by its compressed data
+fn chunk(size_compressed: u32, size_raw: u32, data: &[u8]) -> Vec<u8> {
+    let mut bytes = 1u32.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&size_compressed.to_le_bytes());
+    bytes.extend_from_slice(&size_raw.to_le_bytes());
+    bytes.extend_from_slice(data);
+
+    bytes
+}
+
+fn decode(bytes: Vec<u8>) -> Result<CzFile, CzError> {
+    CzFile::decode(&mut Cursor::new(bytes))
+}
+
+#[test]
+fn unsupported_files() {
+    let result = decode(cz_bytes(5, 1, 1, 32, &[0; 4]));
+    assert!(matches!(result, Err(CzError::UnsupportedVersion(5))));
+
+    let result = decode(cz_bytes(0, 8, 1, 4, &[0; 16 * 4 + 4]));
+    assert!(matches!(result, Err(CzError::UnsupportedDepth(0, 4))));
+}
+
+#[test]
+fn corrupt_compressed_data() {
+    // An element which is not in the dictionary yet
+    let result = decode(cz_bytes(
+        1,
+        1,
+        1,
+        32,
+        &chunk(1, 4, &0x7000u16.to_le_bytes()),
+    ));
+    assert!(matches!(result, Err(CzError::Corrupt(_))));
+
+    // Chunk sizes far larger than the data
+    let result = decode(cz_bytes(1, 1, 1, 32, &chunk(u32::MAX, u32::MAX, &[0; 8])));
+    assert!(matches!(result, Err(CzError::IoError(_))));
+
+    let result = decode(cz_bytes(2, 1, 1, 32, &chunk(u32::MAX, u32::MAX, &[0; 8])));
+    assert!(matches!(result, Err(CzError::IoError(_))));
+
+    // An empty chunk, and one with an element which is not in the dictionary
+    let result = decode(cz_bytes(2, 1, 1, 32, &chunk(0, 0, &[])));
+    assert!(matches!(result, Err(CzError::Corrupt(_))));
+
+    let result = decode(cz_bytes(2, 1, 1, 32, &chunk(3, 4, &[0xFF; 3])));
+    assert!(matches!(result, Err(CzError::Corrupt(_))));
+
+    // Line data shorter than the image
+    for version in [3, 4] {
+        let result = decode(cz_bytes(version, 16, 16, 32, &chunk(1, 1, &[0; 2])));
+        assert!(matches!(result, Err(CzError::Corrupt(_))));
+    }
+}
+
+#[test]
+fn empty_and_mismatched_bitmaps() {
+    let cz = CzFile::from_raw(CzVersion::CZ1, 0, 0, Vec::new());
+    let result = cz.encode(&mut Cursor::new(Vec::new()));
+    assert!(matches!(result, Err(CzError::EmptyBitmap)));
+
+    let cz = CzFile::from_raw(CzVersion::CZ3, 4, 4, vec![0; 4]);
+    let result = cz.encode(&mut Cursor::new(Vec::new()));
+    assert!(matches!(result, Err(CzError::BitmapFormat)));
+}
@@ -0,0 +1,39 @@
+use cz::{
+    CzFile,
+    common::{CzVersion, ExtendedHeader},
+    diff::{BitmapDiff, header_differences},
+};
+
+#[test]
+fn diff_finds_changed_pixels() {
+    let (width, height) = (16u16, 8u16);
+    let bitmap: Vec<u8> = (0..width as usize * height as usize * 4)
+        .map(|i| (i * 5 % 256) as u8)
+        .collect();
+
+    let mut changed = bitmap.clone();
+    for (x, y) in [(3, 2), (9, 5)] {
+        changed[(y * width as usize + x) * 4] ^= 0xFF;
+    }
+
+    let left = CzFile::from_raw(CzVersion::CZ3, width, height, bitmap);
+    let right = CzFile::from_raw(CzVersion::CZ3, width, height, changed)
+        .with_extended_header(ExtendedHeader::new().with_offset((1, 2)));
+
+    let diff = BitmapDiff::from_cz(&left, &right).unwrap();
+    assert_eq!(diff.changed_pixels(), 2);
+    assert!(diff.is_changed(9, 5));
+    assert_eq!(diff.bounding_box(), Some((3, 2, 7, 4)));
+    assert!(BitmapDiff::from_cz(&left, &left).unwrap().is_identical());
+
+    let differences = header_differences(&left, &right);
+    let offset = differences.iter().find(|d| d.field == "offset").unwrap();
+    assert_eq!(offset.left, None);
+    assert_eq!(offset.right.as_deref(), Some("1x2"));
+
+    // Pixels outside of the smaller image count as changed
+    let smaller = vec![0u8; 4 * 4 * 4];
+    let diff = BitmapDiff::new(&smaller, (4, 4), &smaller[..4 * 2 * 4], (4, 2)).unwrap();
+    assert_eq!(diff.changed_pixels(), 8);
+    assert_eq!(diff.bounding_box(), Some((0, 2, 4, 2)));
+}
@@ -0,0 +1,50 @@
+use cz::{
+    CzFile,
+    common::{CzVersion, ExtendedHeader},
+    info::edit_header,
+    io::Cursor,
+};
+
+#[test]
+fn edit_header_keeps_image_data() {
+    let (width, height) = (48u16, 32u16);
+    let bitmap: Vec<u8> = (0..width as usize * height as usize * 4)
+        .map(|i| (i * 13 % 256) as u8)
+        .collect();
+
+    let original_cz = CzFile::from_raw(CzVersion::CZ3, width, height, bitmap)
+        .with_extended_header(ExtendedHeader::new().with_offset((4, 8)));
+
+    let mut cz_bytes = Cursor::new(Vec::new());
+    original_cz.encode(&mut cz_bytes).unwrap();
+    let payload = cz_bytes.get_ref()[28..].to_vec();
+    cz_bytes.set_position(0);
+
+    let mut edited = Vec::new();
+    edit_header(&mut cz_bytes, &mut edited, |common, extended| {
+        common.set_color_block(2);
+
+        let ext = extended.as_mut().unwrap();
+        ext.offset_x = 10;
+        ext.unknown_width = Some(width);
+        ext.unknown_height = Some(height);
+    })
+    .unwrap();
+
+    // The extended header grows, but the image data stays the same
+    assert_eq!(&edited[36..], &payload);
+
+    let decoded_cz = CzFile::decode(&mut Cursor::new(edited)).unwrap();
+    let ext = decoded_cz.extended_header().unwrap();
+    assert_eq!(decoded_cz.header().color_block(), 2);
+    assert_eq!((ext.offset_x, ext.offset_y), (10, 8));
+    assert_eq!(ext.unknown_width, Some(width));
+    assert_eq!(decoded_cz.as_raw(), original_cz.as_raw());
+
+    // Changing anything which affects the image data is refused
+    cz_bytes.set_position(0);
+    let result = edit_header(&mut cz_bytes, &mut Vec::new(), |common, _| {
+        common.set_width(width * 2)
+    });
+    assert!(result.is_err());
+}
@@ -0,0 +1,130 @@
+use cz::{
+    CzFile,
+    common::CzVersion,
+    font::{Font, FontInfo, GlyphMetrics},
+    io::Cursor,
+};
+
+const BLOCK_SIZE: u16 = 8;
+
+/// Build a font info file with three glyphs, mapping 'A' to glyph 1 and 'B'
+/// and a surrogate code unit to glyph 2, with some unknown data at the end
+fn font_info_bytes() -> Vec<u8> {
+    let mut info = Vec::new();
+    for value in [24, BLOCK_SIZE, 3, 3] {
+        info.extend_from_slice(&value.to_le_bytes());
+    }
+    info.extend_from_slice(&[0, 4, 0, 1, 6, 2, 0, 7, 1]);
+
+    let mut char_map = vec![0u16; 0x10000];
+    char_map['A' as usize] = 1;
+    char_map['B' as usize] = 2;
+    char_map[0xD800] = 2;
+    for index in char_map {
+        info.extend_from_slice(&index.to_le_bytes());
+    }
+    info.extend_from_slice(b"tail");
+
+    info
+}
+
+#[test]
+fn font_round_trip() {
+    let info_bytes = font_info_bytes();
+    let info = FontInfo::read(&mut Cursor::new(&info_bytes)).unwrap();
+    assert_eq!(info.glyph_count(), 3);
+    assert_eq!(info.glyph_index('B'), Some(2));
+    assert_eq!(info.glyph_index('z'), Some(0));
+
+    // Two columns of glyphs, each glyph filled with its own color and the
+    // unused last cell left empty
+    let (width, height) = (BLOCK_SIZE * 2, BLOCK_SIZE * 2);
+    let mut bitmap = vec![0u8; width as usize * height as usize * 4];
+    for (i, pixel) in bitmap.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % width as usize, i / width as usize);
+        let glyph = (y / BLOCK_SIZE as usize) * 2 + x / BLOCK_SIZE as usize;
+        if glyph < 3 {
+            pixel.copy_from_slice(&[0xFF, 0xFF, 0xFF, glyph as u8 * 0x40]);
+        }
+    }
+    let atlas = CzFile::from_raw(CzVersion::CZ2, width, height, bitmap);
+
+    let mut font = Font::from_atlas(info, &atlas).unwrap();
+    let glyph = font.glyph('A').unwrap();
+    assert_eq!(glyph.chars(), vec!['A']);
+    assert_eq!(font.glyph('B').unwrap().codes, vec!['B' as u16, 0xD800]);
+    assert_eq!(
+        glyph.metrics,
+        GlyphMetrics {
+            x: 1,
+            width: 6,
+            y: 2
+        }
+    );
+    assert!(glyph.bitmap.chunks_exact(4).all(|p| p[3] == 0x40));
+
+    // Unchanged glyphs build the same atlas and info file
+    let (rebuilt_info, rebuilt_atlas) = font.build().unwrap();
+    let mut rebuilt_bytes = Vec::new();
+    rebuilt_info.write_into(&mut rebuilt_bytes).unwrap();
+    assert_eq!(rebuilt_bytes, info_bytes);
+    assert_eq!(rebuilt_atlas.as_raw()[..], atlas.as_raw()[..]);
+
+    // Add two glyphs, so the atlas grows by a row
+    let mut new_glyph = font.glyphs()[2].clone();
+    new_glyph.codes = vec!['C' as u16, 'あ' as u16];
+    font.glyphs_mut().push(new_glyph.clone());
+    new_glyph.codes = vec!['D' as u16];
+    font.glyphs_mut().push(new_glyph);
+
+    let (rebuilt_info, rebuilt_atlas) = font.build().unwrap();
+    assert_eq!(rebuilt_info.glyph_count(), 5);
+    assert_eq!(rebuilt_info.glyph_count_2(), 5);
+    assert_eq!(rebuilt_info.glyph_index('あ'), Some(3));
+    assert_eq!(rebuilt_atlas.header().version(), CzVersion::CZ2);
+    assert_eq!(rebuilt_atlas.header().height(), BLOCK_SIZE * 3);
+
+    let mut atlas_bytes = Cursor::new(Vec::new());
+    rebuilt_atlas.encode(&mut atlas_bytes).unwrap();
+    atlas_bytes.set_position(0);
+    let decoded_atlas = CzFile::decode(&mut atlas_bytes).unwrap();
+
+    let mut info_bytes = Vec::new();
+    rebuilt_info.write_into(&mut info_bytes).unwrap();
+    let decoded_info = FontInfo::read(&mut Cursor::new(info_bytes)).unwrap();
+
+    let decoded_font = Font::from_atlas(decoded_info, &decoded_atlas).unwrap();
+    assert_eq!(
+        decoded_font.glyph('D').unwrap().bitmap,
+        font.glyphs()[2].bitmap
+    );
+    assert_eq!(decoded_font.glyph('C').unwrap().chars(), vec!['C', 'あ']);
+}
+
+#[test]
+fn build_keeps_atlas_width() {
+    let info = FontInfo::read(&mut Cursor::new(font_info_bytes())).unwrap();
+
+    // Room for two columns of glyphs, with four pixels to spare
+    let (width, height) = (BLOCK_SIZE * 2 + 4, BLOCK_SIZE * 2);
+    let bitmap = vec![0xFF; width as usize * height as usize * 4];
+    let atlas = CzFile::from_raw(CzVersion::CZ2, width, height, bitmap);
+
+    let font = Font::from_atlas(info, &atlas).unwrap();
+    let (rebuilt_info, rebuilt_atlas) = font.build().unwrap();
+    assert_eq!(rebuilt_atlas.header().width(), width);
+    assert_eq!(rebuilt_atlas.header().height(), height);
+
+    let mut atlas_bytes = Cursor::new(Vec::new());
+    rebuilt_atlas.encode(&mut atlas_bytes).unwrap();
+    atlas_bytes.set_position(0);
+    let decoded_atlas = CzFile::decode(&mut atlas_bytes).unwrap();
+
+    // The glyphs are where they were, the spare space is empty
+    let decoded_font = Font::from_atlas(rebuilt_info, &decoded_atlas).unwrap();
+    for (decoded, glyph) in decoded_font.glyphs().iter().zip(font.glyphs()) {
+        assert_eq!(decoded.bitmap, glyph.bitmap);
+    }
+    let row = &decoded_atlas.as_raw()[..width as usize * 4];
+    assert!(row[BLOCK_SIZE as usize * 2 * 4..].iter().all(|&b| b == 0));
+}
@@ -0,0 +1,39 @@
+use cz::{
+    CzFile, GrayscaleRamp,
+    common::{CzError, CzVersion},
+    io::Cursor,
+};
+
+#[test]
+fn luma8_round_trip() {
+    let (width, height) = (64u16, 48u16);
+    let luma: Vec<u8> = (0..width as usize * height as usize)
+        .map(|i| (i * 7 % 256) as u8)
+        .collect();
+
+    for ramp in [GrayscaleRamp::Alpha, GrayscaleRamp::Luma] {
+        for version in [CzVersion::CZ0, CzVersion::CZ1, CzVersion::CZ3] {
+            let original_cz = CzFile::from_luma8(version, width, height, &luma, ramp).unwrap();
+
+            let mut cz_bytes = Cursor::new(Vec::new());
+            original_cz.encode(&mut cz_bytes).unwrap();
+            cz_bytes.set_position(0);
+
+            let decoded_cz = CzFile::decode(&mut cz_bytes).unwrap();
+
+            assert_eq!(decoded_cz.grayscale_ramp(), Some(ramp));
+            assert_eq!(decoded_cz.to_luma8().unwrap(), luma);
+        }
+    }
+}
+
+#[test]
+fn luma8_wrong_size() {
+    let luma = vec![0u8; 63];
+
+    let result = CzFile::from_luma8(CzVersion::CZ3, 8, 8, &luma, GrayscaleRamp::Alpha);
+    assert!(matches!(result, Err(CzError::BitmapFormat)));
+
+    let mut cz = CzFile::from_luma8(CzVersion::CZ3, 8, 8, &[0; 64], GrayscaleRamp::Alpha).unwrap();
+    assert!(matches!(cz.set_luma8(&luma), Err(CzError::BitmapFormat)));
+}
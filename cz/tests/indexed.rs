@@ -0,0 +1,70 @@
+use cz::{CzFile, Palette, RGBA8, common::CzVersion, io::Cursor};
+
+#[test]
+fn indexed_round_trip() {
+    let (width, height) = (32u16, 16u16);
+    let indices: Vec<u8> = (0..width as usize * height as usize)
+        .map(|i| (i % 256) as u8)
+        .collect();
+
+    // Duplicate colors must keep their own indices
+    let colors = (0..=0xFF)
+        .map(|i| RGBA8::new(i / 2, 0x10, 0x20, 0xFF))
+        .collect();
+    let palette = Palette::new(colors);
+
+    for version in [
+        CzVersion::CZ0,
+        CzVersion::CZ1,
+        CzVersion::CZ2,
+        CzVersion::CZ3,
+    ] {
+        let original_cz =
+            CzFile::from_indexed(version, width, height, indices.clone(), palette.clone()).unwrap();
+
+        let mut cz_bytes = Cursor::new(Vec::new());
+        original_cz.encode(&mut cz_bytes).unwrap();
+        cz_bytes.set_position(0);
+
+        let decoded_cz = CzFile::decode(&mut cz_bytes).unwrap();
+
+        assert_eq!(decoded_cz.indices().unwrap(), &indices);
+        assert_eq!(
+            decoded_cz.palette().as_ref().unwrap().colors(),
+            palette.colors()
+        );
+        assert_eq!(decoded_cz.as_raw(), original_cz.as_raw());
+    }
+}
+
+#[cfg(not(feature = "imagequant"))]
+#[test]
+fn generated_palette_without_quantizing() {
+    use cz::common::CzError;
+
+    let (width, height) = (32u16, 16u16);
+    let bitmap: Vec<u8> = (0..width as usize * height as usize)
+        .flat_map(|i| [(i % 200) as u8, 0x10, 0x20, 0x80])
+        .collect();
+
+    let mut original_cz = CzFile::from_raw(CzVersion::CZ3, width, height, bitmap);
+    original_cz.header_mut().set_depth(8);
+
+    let mut cz_bytes = Cursor::new(Vec::new());
+    original_cz.encode(&mut cz_bytes).unwrap();
+    cz_bytes.set_position(0);
+
+    let decoded_cz = CzFile::decode(&mut cz_bytes).unwrap();
+    assert_eq!(decoded_cz.as_raw(), original_cz.as_raw());
+
+    // Too many colors to fit in a palette without quantizing
+    let bitmap: Vec<u8> = (0..width as usize * height as usize)
+        .flat_map(|i| [(i % 256) as u8, (i / 256) as u8, 0x20, 0x80])
+        .collect();
+
+    let mut many_colors = CzFile::from_raw(CzVersion::CZ3, width, height, bitmap);
+    many_colors.header_mut().set_depth(8);
+
+    let result = many_colors.encode(&mut Cursor::new(Vec::new()));
+    assert!(matches!(result, Err(CzError::TooManyColors)));
+}
@@ -0,0 +1,26 @@
+use cz::{
+    CzFile,
+    common::{CzVersion, ExtendedHeader},
+    io::Cursor,
+};
+
+#[test]
+fn optimize_keeps_pixels() {
+    let (width, height) = (64u16, 64u16);
+    let bitmap: Vec<u8> = (0..width as usize * height as usize)
+        .flat_map(|i| [(i / 64) as u8, 0x80, (i % 64) as u8, (i % 3) as u8 * 0x7F])
+        .collect();
+
+    let original_cz = CzFile::from_raw(CzVersion::CZ0, width, height, bitmap)
+        .with_extended_header(ExtendedHeader::new().with_offset((12, 34)));
+
+    let mut cz_bytes = Cursor::new(Vec::new());
+    original_cz.encode(&mut cz_bytes).unwrap();
+
+    let optimized = original_cz.optimize().unwrap();
+    assert!(optimized.bytes.len() < cz_bytes.get_ref().len());
+
+    let decoded_cz = CzFile::decode(&mut Cursor::new(optimized.bytes)).unwrap();
+    assert_eq!(decoded_cz.as_raw(), original_cz.as_raw());
+    assert_eq!(decoded_cz.extended_header().unwrap().offset_x, 12);
+}
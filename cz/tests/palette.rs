@@ -0,0 +1,47 @@
+#![cfg(feature = "png")]
+
+use std::io::Cursor;
+
+use cz::{Palette, RGBA8, common::CzError};
+
+#[test]
+fn png_swatch_keeps_color_count() {
+    // The last color is the same as the padding of the swatch
+    let colors: Vec<RGBA8> = (0..100)
+        .map(|i| RGBA8::new(i, 0x10, 0x20, 0xFF))
+        .chain([RGBA8::new(0, 0, 0, 0)])
+        .collect();
+    let palette = Palette::new(colors);
+
+    let mut png_bytes = Vec::new();
+    palette.write_png(&mut png_bytes).unwrap();
+
+    let loaded = Palette::from_png(Cursor::new(png_bytes)).unwrap();
+    assert_eq!(loaded.colors(), palette.colors());
+}
+
+#[test]
+fn swatch_padding_is_removed() {
+    let colors: Vec<RGBA8> = (0..100).map(|i| RGBA8::new(i, 0x10, 0x20, 0xFF)).collect();
+    let palette = Palette::new(colors);
+
+    let (width, height, rgba) = palette.to_swatch();
+    assert_eq!((width, height), (16, 7));
+
+    let loaded = Palette::from_swatch(&rgba, None).unwrap();
+    assert_eq!(loaded.colors(), palette.colors());
+}
+
+#[test]
+fn unknown_palette_type_is_not_created() {
+    let path = std::env::temp_dir().join(format!("cz_palette_{}.txt", std::process::id()));
+    let palette = Palette::new(vec![RGBA8::new(1, 2, 3, 4)]);
+
+    let err = palette.save(&path).unwrap_err();
+    assert!(
+        matches!(err, CzError::UnknownPaletteType(ref e) if e == "txt"),
+        "{:?}",
+        err
+    );
+    assert!(!path.exists());
+}
@@ -0,0 +1,122 @@
+use cz::{
+    CzFile, GrayscaleRamp, ResizeFilter,
+    common::{CzError, CzVersion, ExtendedHeader},
+    io::Cursor,
+    resize::resize_rgba,
+};
+
+#[test]
+fn resize_scales_extended_header() {
+    let (width, height) = (64u16, 36u16);
+    let bitmap: Vec<u8> = (0..width as usize * height as usize)
+        .flat_map(|i| {
+            [
+                (i % 64) as u8 * 4,
+                0x80,
+                0x40,
+                if i % 64 < 32 { 0xFF } else { 0 },
+            ]
+        })
+        .collect();
+
+    let ext = ExtendedHeader::new()
+        .with_offset((10, 20))
+        .with_crop((64, 36))
+        .with_bounds((1280, 720));
+
+    for filter in [
+        ResizeFilter::Nearest,
+        ResizeFilter::Bilinear,
+        ResizeFilter::Bicubic,
+    ] {
+        let mut cz = CzFile::from_raw(CzVersion::CZ3, width, height, bitmap.clone())
+            .with_extended_header(ext);
+        cz.resize(96, 54, filter).unwrap();
+
+        assert_eq!((cz.header().width(), cz.header().height()), (96, 54));
+        assert_eq!(cz.as_raw().len(), 96 * 54 * 4);
+
+        let new_ext = cz.extended_header().unwrap();
+        assert_eq!((new_ext.offset_x, new_ext.offset_y), (15, 30));
+        assert_eq!((new_ext.crop_width, new_ext.crop_height), (96, 54));
+        assert_eq!((new_ext.bounds_width, new_ext.bounds_height), (1920, 1080));
+
+        // Fully transparent pixels must not bleed their color into the edge
+        let row = &cz.as_raw()[..96 * 4];
+        assert!(row[..40 * 4].chunks_exact(4).all(|p| p[3] == 0xFF));
+        assert!(row[56 * 4..].chunks_exact(4).all(|p| p[3] == 0));
+        assert!(
+            row.chunks_exact(4)
+                .filter(|p| p[3] != 0)
+                .all(|p| p[1] == 0x80)
+        );
+
+        let mut cz_bytes = Cursor::new(Vec::new());
+        cz.encode(&mut cz_bytes).unwrap();
+        cz_bytes.set_position(0);
+
+        let decoded_cz = CzFile::decode(&mut cz_bytes).unwrap();
+        assert_eq!(decoded_cz.as_raw(), cz.as_raw());
+    }
+}
+
+#[test]
+fn nearest_resize_keeps_palette() {
+    let (width, height) = (16u16, 16u16);
+    let bitmap: Vec<u8> = (0..width as usize * height as usize)
+        .flat_map(|i| [(i % 4) as u8 * 0x40, 0, 0, 0x80])
+        .collect();
+
+    let mut cz = CzFile::from_raw(CzVersion::CZ1, width, height, bitmap);
+    cz.set_auto_depth();
+    let palette = cz.palette().clone().unwrap();
+
+    cz.resize(32, 8, ResizeFilter::Nearest).unwrap();
+    assert_eq!(cz.palette().as_ref().unwrap().colors(), palette.colors());
+
+    // Doubling the width repeats every pixel
+    assert_eq!(
+        &cz.as_raw()[..16],
+        &[
+            0, 0, 0, 0x80, 0, 0, 0, 0x80, 0x40, 0, 0, 0x80, 0x40, 0, 0, 0x80
+        ]
+    );
+
+    assert!(cz.resize(0, 8, ResizeFilter::Nearest).is_err());
+}
+
+#[test]
+fn resized_mask_keeps_ramp() {
+    let (width, height) = (16u16, 16u16);
+    let luma: Vec<u8> = (0..width as usize * height as usize)
+        .map(|i| (i % 16) as u8 * 0x11)
+        .collect();
+
+    let mut cz =
+        CzFile::from_luma8(CzVersion::CZ1, width, height, &luma, GrayscaleRamp::Alpha).unwrap();
+    cz.resize(24, 8, ResizeFilter::Bilinear).unwrap();
+
+    assert_eq!(cz.grayscale_ramp(), Some(GrayscaleRamp::Alpha));
+    assert_eq!(cz.palette().as_ref().unwrap().len(), 256);
+
+    // The ramp starts fully transparent and ends fully opaque
+    let resized = cz.to_luma8().unwrap();
+    assert_eq!(resized.len(), 24 * 8);
+    assert_eq!((resized[0], resized[23]), (0, 0xFF));
+    assert!(resized[..24].windows(2).all(|p| p[0] <= p[1]));
+}
+
+#[test]
+fn resize_checks_bitmap_size() {
+    let short = resize_rgba(&[0; 12], (2, 2), (4, 4), ResizeFilter::Nearest);
+    assert!(matches!(short, Err(CzError::BitmapFormat)));
+
+    let empty = resize_rgba(&[], (0, 4), (4, 4), ResizeFilter::Nearest);
+    assert!(matches!(empty, Err(CzError::EmptyBitmap)));
+
+    // A failed resize leaves the image as it was
+    let mut cz = CzFile::from_raw(CzVersion::CZ3, 4, 4, vec![0; 12]);
+    let err = cz.resize(8, 8, ResizeFilter::Bilinear).unwrap_err();
+    assert!(matches!(err, CzError::BitmapFormat), "{:?}", err);
+    assert_eq!((cz.header().width(), cz.header().height()), (4, 4));
+}
@@ -0,0 +1,327 @@
+#ifndef LUCA_CAPI_H
+#define LUCA_CAPI_H
+
+/* This file is generated by cbindgen from the luca_capi crate, do not edit it */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The result of a call to the API
+ */
+typedef enum LucaStatus {
+  /**
+   * The call succeeded
+   */
+  LUCA_STATUS_OK = 0,
+  /**
+   * A required pointer was null
+   */
+  LUCA_STATUS_NULL_POINTER = 1,
+  /**
+   * An argument was invalid, like a path which is not UTF-8 or a bitmap
+   * of the wrong size
+   */
+  LUCA_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * A file could not be read or written
+   */
+  LUCA_STATUS_IO = 3,
+  /**
+   * The data is not a valid CZ image or PAK archive
+   */
+  LUCA_STATUS_INVALID_DATA = 4,
+  /**
+   * An entry index is out of range
+   */
+  LUCA_STATUS_NOT_FOUND = 5,
+  /**
+   * An unexpected internal error, which is a bug in the library. Only
+   * returned if the library is built to unwind on panic, otherwise the
+   * process aborts.
+   */
+  LUCA_STATUS_PANIC = 6,
+} LucaStatus;
+
+/**
+ * An opaque handle to a decoded CZ# image
+ */
+typedef struct LucaCz LucaCz;
+
+/**
+ * An opaque handle to a decoded PAK archive
+ */
+typedef struct LucaPak LucaPak;
+
+/**
+ * Bytes allocated by the library, which must be freed with
+ * [`luca_buffer_free()`]
+ */
+typedef struct LucaBuffer {
+  uint8_t *data;
+  size_t len;
+} LucaBuffer;
+
+/**
+ * Information about an entry in a PAK archive
+ */
+typedef struct LucaPakEntry {
+  /**
+   * The ID of the entry, which some games use to refer to it
+   */
+  uint32_t id;
+  /**
+   * Length of the entry data in bytes
+   */
+  size_t len;
+  /**
+   * Name of the entry, or null if the archive has no names. The name
+   * belongs to the archive, and is valid until the archive is freed.
+   */
+  const char *name;
+} LucaPakEntry;
+
+/**
+ * Information from the header of a CZ# image
+ */
+typedef struct LucaCzInfo {
+  /**
+   * CZ# version, from 0 to 5
+   */
+  uint8_t version;
+  uint16_t width;
+  uint16_t height;
+  /**
+   * Bit depth, 8, 24 or 32
+   */
+  uint16_t depth;
+  uint8_t color_block;
+  /**
+   * Whether the image has an extended header, the fields below are 0 if
+   * it does not
+   */
+  bool has_extended_header;
+  uint16_t offset_x;
+  uint16_t offset_y;
+  uint16_t crop_width;
+  uint16_t crop_height;
+  uint16_t bounds_width;
+  uint16_t bounds_height;
+} LucaCzInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Free a buffer returned by the library. Freeing a buffer with a null data
+ * pointer does nothing.
+ *
+ * # Safety
+ * The buffer must have been returned by this library, and must not be used
+ * or freed again afterwards.
+ */
+void luca_buffer_free(struct LucaBuffer buffer);
+
+/**
+ * Get a message describing the last error on this thread, or null if the
+ * last call succeeded. The message is valid until the next call into the
+ * library on this thread.
+ */
+const char *luca_last_error(void);
+
+/**
+ * Open and decode a PAK archive file.
+ *
+ * # Safety
+ * `path` must be a nul terminated string, and `out` must be valid for
+ * writes. The archive written to `out` must be freed with [`pak_free()`].
+ */
+enum LucaStatus pak_open(const char *path, struct LucaPak **out);
+
+/**
+ * Decode a PAK archive from bytes in memory.
+ *
+ * # Safety
+ * `data` must be valid for reads of `len` bytes, and `out` must be valid for
+ * writes. The archive written to `out` must be freed with [`pak_free()`].
+ */
+enum LucaStatus pak_decode(const uint8_t *data, size_t len, struct LucaPak **out);
+
+/**
+ * Get the number of entries in an archive.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `out` must be valid for
+ * writes.
+ */
+enum LucaStatus pak_entry_count(const struct LucaPak *pak, size_t *out);
+
+/**
+ * Get information about an entry in an archive.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `out` must be valid for
+ * writes.
+ */
+enum LucaStatus pak_entry_info(const struct LucaPak *pak, size_t index, struct LucaPakEntry *out);
+
+/**
+ * Get the data of an entry in an archive. The data belongs to the archive,
+ * and is valid until the archive is changed or freed.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `out_data` and `out_len`
+ * must be valid for writes.
+ */
+enum LucaStatus pak_entry_data(const struct LucaPak *pak,
+                               size_t index,
+                               const uint8_t **out_data,
+                               size_t *out_len);
+
+/**
+ * Save the data of an entry in an archive to a file.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `path` must be a nul
+ * terminated string.
+ */
+enum LucaStatus pak_extract(const struct LucaPak *pak, size_t index, const char *path);
+
+/**
+ * Replace the data of an entry in an archive, moving the entries after it
+ * to fit.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `data` must be valid for
+ * reads of `len` bytes.
+ */
+enum LucaStatus pak_replace(struct LucaPak *pak, size_t index, const uint8_t *data, size_t len);
+
+/**
+ * Encode an archive into a PAK file in memory.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `out` must be valid for
+ * writes. The buffer written to `out` must be freed with
+ * `luca_buffer_free()`.
+ */
+enum LucaStatus pak_encode(const struct LucaPak *pak, struct LucaBuffer *out);
+
+/**
+ * Encode an archive and save it to a PAK file.
+ *
+ * # Safety
+ * `pak` must be an archive from this library, and `path` must be a nul
+ * terminated string.
+ */
+enum LucaStatus pak_save(const struct LucaPak *pak, const char *path);
+
+/**
+ * Free an archive. Freeing a null pointer does nothing.
+ *
+ * # Safety
+ * `pak` must be null or an archive from this library, and must not be used
+ * again afterwards.
+ */
+void pak_free(struct LucaPak *pak);
+
+/**
+ * Open and decode a CZ# image file.
+ *
+ * # Safety
+ * `path` must be a nul terminated string, and `out` must be valid for
+ * writes. The image written to `out` must be freed with [`cz_free()`].
+ */
+enum LucaStatus cz_open(const char *path, struct LucaCz **out);
+
+/**
+ * Decode a CZ# image from bytes in memory.
+ *
+ * # Safety
+ * `data` must be valid for reads of `len` bytes, and `out` must be valid for
+ * writes. The image written to `out` must be freed with [`cz_free()`].
+ */
+enum LucaStatus cz_decode(const uint8_t *data, size_t len, struct LucaCz **out);
+
+/**
+ * Create a 32-bit CZ# image of a version from 0 to 4 from an RGBA bitmap,
+ * which must be `width * height * 4` bytes long.
+ *
+ * # Safety
+ * `rgba` must be valid for reads of `len` bytes, and `out` must be valid for
+ * writes. The image written to `out` must be freed with [`cz_free()`].
+ */
+enum LucaStatus cz_from_rgba(uint8_t version,
+                             uint16_t width,
+                             uint16_t height,
+                             const uint8_t *rgba,
+                             size_t len,
+                             struct LucaCz **out);
+
+/**
+ * Get the header information of an image.
+ *
+ * # Safety
+ * `cz` must be an image from this library, and `out` must be valid for
+ * writes.
+ */
+enum LucaStatus cz_info(const struct LucaCz *cz, struct LucaCzInfo *out);
+
+/**
+ * Get the RGBA bitmap of an image, which is `width * height * 4` bytes long.
+ * The bitmap belongs to the image, and is valid until the image is changed
+ * or freed.
+ *
+ * # Safety
+ * `cz` must be an image from this library, and `out_data` and `out_len`
+ * must be valid for writes.
+ */
+enum LucaStatus cz_rgba(const struct LucaCz *cz, const uint8_t **out_data, size_t *out_len);
+
+/**
+ * Replace the RGBA bitmap of an image, which must be the same size as the
+ * current one. The palette of 8-bit images is generated again on encode.
+ *
+ * # Safety
+ * `cz` must be an image from this library, and `rgba` must be valid for
+ * reads of `len` bytes.
+ */
+enum LucaStatus cz_set_rgba(struct LucaCz *cz, const uint8_t *rgba, size_t len);
+
+/**
+ * Encode an image into a CZ# file in memory.
+ *
+ * # Safety
+ * `cz` must be an image from this library, and `out` must be valid for
+ * writes. The buffer written to `out` must be freed with
+ * `luca_buffer_free()`.
+ */
+enum LucaStatus cz_encode(const struct LucaCz *cz, struct LucaBuffer *out);
+
+/**
+ * Encode an image and save it to a CZ# file.
+ *
+ * # Safety
+ * `cz` must be an image from this library, and `path` must be a nul
+ * terminated string.
+ */
+enum LucaStatus cz_save(const struct LucaCz *cz, const char *path);
+
+/**
+ * Free an image. Freeing a null pointer does nothing.
+ *
+ * # Safety
+ * `cz` must be null or an image from this library, and must not be used
+ * again afterwards.
+ */
+void cz_free(struct LucaCz *cz);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LUCA_CAPI_H */
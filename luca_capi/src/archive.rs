@@ -0,0 +1,257 @@
+//! Functions for PAK archives
+
+use std::{
+    ffi::{CString, c_char},
+    io::Cursor,
+    path::PathBuf,
+    ptr,
+};
+
+use luca_pak::{Pak, PakError, PakLimits};
+
+use crate::{
+    LucaBuffer, LucaStatus, ffi_call, free_handle, from_bytes, from_mut_ptr, from_path, from_ptr,
+    write_buffer, write_handle, write_out,
+};
+
+/// An opaque handle to a decoded PAK archive
+pub struct LucaPak {
+    pak: Pak,
+
+    /// Entry names as C strings, so they can be borrowed by the caller
+    names: Vec<Option<CString>>,
+}
+
+impl LucaPak {
+    fn new(pak: Pak) -> Self {
+        let names = pak
+            .entries()
+            .iter()
+            .map(|e| {
+                e.name()
+                    .as_ref()
+                    .and_then(|n| CString::new(n.as_bytes()).ok())
+            })
+            .collect();
+
+        Self { pak, names }
+    }
+}
+
+/// Information about an entry in a PAK archive
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LucaPakEntry {
+    /// The ID of the entry, which some games use to refer to it
+    pub id: u32,
+
+    /// Length of the entry data in bytes
+    pub len: usize,
+
+    /// Name of the entry, or null if the archive has no names. The name
+    /// belongs to the archive, and is valid until the archive is freed.
+    pub name: *const c_char,
+}
+
+/// Open and decode a PAK archive file.
+///
+/// # Safety
+/// `path` must be a nul terminated string, and `out` must be valid for
+/// writes. The archive written to `out` must be freed with [`pak_free()`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_open(path: *const c_char, out: *mut *mut LucaPak) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let path = unsafe { from_path(path) }?;
+        let pak = Pak::open(path)?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_handle(out, LucaPak::new(pak)) }
+    })
+}
+
+/// Decode a PAK archive from bytes in memory.
+///
+/// # Safety
+/// `data` must be valid for reads of `len` bytes, and `out` must be valid for
+/// writes. The archive written to `out` must be freed with [`pak_free()`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_decode(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut LucaPak,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let data = unsafe { from_bytes(data, len) }?;
+        let pak = Pak::decode(&mut Cursor::new(data), PathBuf::new(), PakLimits::default())?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_handle(out, LucaPak::new(pak)) }
+    })
+}
+
+/// Get the number of entries in an archive.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `out` must be valid for
+/// writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_entry_count(pak: *const LucaPak, out: *mut usize) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_ptr(pak) }?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_out(out, pak.pak.entries().len()) }
+    })
+}
+
+/// Get information about an entry in an archive.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `out` must be valid for
+/// writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_entry_info(
+    pak: *const LucaPak,
+    index: usize,
+    out: *mut LucaPakEntry,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_ptr(pak) }?;
+        let entry = pak.pak.entries().get(index).ok_or(PakError::IndexError)?;
+
+        let info = LucaPakEntry {
+            id: entry.id(),
+            len: entry.len(),
+            name: pak.names[index]
+                .as_ref()
+                .map_or(ptr::null(), |n| n.as_ptr()),
+        };
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_out(out, info) }
+    })
+}
+
+/// Get the data of an entry in an archive. The data belongs to the archive,
+/// and is valid until the archive is changed or freed.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `out_data` and `out_len`
+/// must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_entry_data(
+    pak: *const LucaPak,
+    index: usize,
+    out_data: *mut *const u8,
+    out_len: *mut usize,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_ptr(pak) }?;
+        let entry = pak.pak.entries().get(index).ok_or(PakError::IndexError)?;
+        let data = entry.as_bytes()?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe {
+            write_out(out_data, data.as_ptr())?;
+            write_out(out_len, data.len())
+        }
+    })
+}
+
+/// Save the data of an entry in an archive to a file.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `path` must be a nul
+/// terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_extract(
+    pak: *const LucaPak,
+    index: usize,
+    path: *const c_char,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_ptr(pak) }?;
+        // SAFETY: Guaranteed by the caller
+        let path = unsafe { from_path(path) }?;
+        let entry = pak.pak.entries().get(index).ok_or(PakError::IndexError)?;
+
+        Ok(std::fs::write(path, entry.as_bytes()?).map_err(PakError::IoError)?)
+    })
+}
+
+/// Replace the data of an entry in an archive, moving the entries after it
+/// to fit.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `data` must be valid for
+/// reads of `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_replace(
+    pak: *mut LucaPak,
+    index: usize,
+    data: *const u8,
+    len: usize,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_mut_ptr(pak) }?;
+        // SAFETY: Guaranteed by the caller
+        let data = unsafe { from_bytes(data, len) }?;
+
+        Ok(pak.pak.replace(index, data)?)
+    })
+}
+
+/// Encode an archive into a PAK file in memory.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `out` must be valid for
+/// writes. The buffer written to `out` must be freed with
+/// `luca_buffer_free()`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_encode(pak: *const LucaPak, out: *mut LucaBuffer) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_ptr(pak) }?;
+
+        let mut bytes = Vec::new();
+        pak.pak.encode(&mut bytes)?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_buffer(out, bytes) }
+    })
+}
+
+/// Encode an archive and save it to a PAK file.
+///
+/// # Safety
+/// `pak` must be an archive from this library, and `path` must be a nul
+/// terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_save(pak: *const LucaPak, path: *const c_char) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let pak = unsafe { from_ptr(pak) }?;
+        // SAFETY: Guaranteed by the caller
+        let path = unsafe { from_path(path) }?;
+
+        Ok(pak.pak.save(path)?)
+    })
+}
+
+/// Free an archive. Freeing a null pointer does nothing.
+///
+/// # Safety
+/// `pak` must be null or an archive from this library, and must not be used
+/// again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pak_free(pak: *mut LucaPak) {
+    // SAFETY: Guaranteed by the caller
+    unsafe { free_handle(pak) }
+}
@@ -0,0 +1,251 @@
+//! Functions for CZ# images
+
+use std::{ffi::c_char, io::Cursor};
+
+use cz::{
+    CzFile,
+    common::{CzError, CzVersion},
+};
+
+use crate::{
+    Failure, LucaBuffer, LucaStatus, ffi_call, free_handle, from_bytes, from_mut_ptr, from_path,
+    from_ptr, write_buffer, write_handle, write_out,
+};
+
+/// An opaque handle to a decoded CZ# image
+pub struct LucaCz(CzFile);
+
+/// Information from the header of a CZ# image
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LucaCzInfo {
+    /// CZ# version, from 0 to 5
+    pub version: u8,
+    pub width: u16,
+    pub height: u16,
+
+    /// Bit depth, 8, 24 or 32
+    pub depth: u16,
+    pub color_block: u8,
+
+    /// Whether the image has an extended header, the fields below are 0 if
+    /// it does not
+    pub has_extended_header: bool,
+    pub offset_x: u16,
+    pub offset_y: u16,
+    pub crop_width: u16,
+    pub crop_height: u16,
+    pub bounds_width: u16,
+    pub bounds_height: u16,
+}
+
+/// Open and decode a CZ# image file.
+///
+/// # Safety
+/// `path` must be a nul terminated string, and `out` must be valid for
+/// writes. The image written to `out` must be freed with [`cz_free()`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_open(path: *const c_char, out: *mut *mut LucaCz) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let path = unsafe { from_path(path) }?;
+        let cz = cz::open(path)?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_handle(out, LucaCz(cz)) }
+    })
+}
+
+/// Decode a CZ# image from bytes in memory.
+///
+/// # Safety
+/// `data` must be valid for reads of `len` bytes, and `out` must be valid for
+/// writes. The image written to `out` must be freed with [`cz_free()`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_decode(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut LucaCz,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let data = unsafe { from_bytes(data, len) }?;
+        let cz = CzFile::decode(&mut Cursor::new(data))?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_handle(out, LucaCz(cz)) }
+    })
+}
+
+/// Create a 32-bit CZ# image of a version from 0 to 4 from an RGBA bitmap,
+/// which must be `width * height * 4` bytes long.
+///
+/// # Safety
+/// `rgba` must be valid for reads of `len` bytes, and `out` must be valid for
+/// writes. The image written to `out` must be freed with [`cz_free()`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_from_rgba(
+    version: u8,
+    width: u16,
+    height: u16,
+    rgba: *const u8,
+    len: usize,
+    out: *mut *mut LucaCz,
+) -> LucaStatus {
+    ffi_call(|| {
+        let version = match CzVersion::try_from(version) {
+            Ok(CzVersion::CZ5) | Err(_) => {
+                return Err(Failure::new(
+                    LucaStatus::InvalidArgument,
+                    format!("CZ{} images cannot be created", version),
+                ));
+            }
+            Ok(v) => v,
+        };
+
+        // SAFETY: Guaranteed by the caller
+        let rgba = unsafe { from_bytes(rgba, len) }?;
+        if rgba.len() != width as usize * height as usize * 4 {
+            return Err(CzError::BitmapFormat.into());
+        }
+
+        let cz = CzFile::from_raw(version, width, height, rgba.to_vec());
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_handle(out, LucaCz(cz)) }
+    })
+}
+
+/// Get the header information of an image.
+///
+/// # Safety
+/// `cz` must be an image from this library, and `out` must be valid for
+/// writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_info(cz: *const LucaCz, out: *mut LucaCzInfo) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let LucaCz(cz) = unsafe { from_ptr(cz) }?;
+        let header = cz.header();
+
+        let mut info = LucaCzInfo {
+            version: header.version() as u8,
+            width: header.width(),
+            height: header.height(),
+            depth: header.depth(),
+            color_block: header.color_block(),
+            ..Default::default()
+        };
+
+        if let Some(ext) = cz.extended_header() {
+            info.has_extended_header = true;
+            info.offset_x = ext.offset_x;
+            info.offset_y = ext.offset_y;
+            info.crop_width = ext.crop_width;
+            info.crop_height = ext.crop_height;
+            info.bounds_width = ext.bounds_width;
+            info.bounds_height = ext.bounds_height;
+        }
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_out(out, info) }
+    })
+}
+
+/// Get the RGBA bitmap of an image, which is `width * height * 4` bytes long.
+/// The bitmap belongs to the image, and is valid until the image is changed
+/// or freed.
+///
+/// # Safety
+/// `cz` must be an image from this library, and `out_data` and `out_len`
+/// must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_rgba(
+    cz: *const LucaCz,
+    out_data: *mut *const u8,
+    out_len: *mut usize,
+) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let LucaCz(cz) = unsafe { from_ptr(cz) }?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe {
+            write_out(out_data, cz.as_raw().as_ptr())?;
+            write_out(out_len, cz.as_raw().len())
+        }
+    })
+}
+
+/// Replace the RGBA bitmap of an image, which must be the same size as the
+/// current one. The palette of 8-bit images is generated again on encode.
+///
+/// # Safety
+/// `cz` must be an image from this library, and `rgba` must be valid for
+/// reads of `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_set_rgba(cz: *mut LucaCz, rgba: *const u8, len: usize) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let LucaCz(cz) = unsafe { from_mut_ptr(cz) }?;
+        // SAFETY: Guaranteed by the caller
+        let rgba = unsafe { from_bytes(rgba, len) }?;
+
+        if rgba.len() != cz.as_raw().len() {
+            return Err(CzError::BitmapFormat.into());
+        }
+
+        cz.set_bitmap(rgba.to_vec());
+        cz.clear_palette();
+
+        Ok(())
+    })
+}
+
+/// Encode an image into a CZ# file in memory.
+///
+/// # Safety
+/// `cz` must be an image from this library, and `out` must be valid for
+/// writes. The buffer written to `out` must be freed with
+/// `luca_buffer_free()`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_encode(cz: *const LucaCz, out: *mut LucaBuffer) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let LucaCz(cz) = unsafe { from_ptr(cz) }?;
+
+        let mut bytes = Cursor::new(Vec::new());
+        cz.encode(&mut bytes)?;
+
+        // SAFETY: Guaranteed by the caller
+        unsafe { write_buffer(out, bytes.into_inner()) }
+    })
+}
+
+/// Encode an image and save it to a CZ# file.
+///
+/// # Safety
+/// `cz` must be an image from this library, and `path` must be a nul
+/// terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_save(cz: *const LucaCz, path: *const c_char) -> LucaStatus {
+    ffi_call(|| {
+        // SAFETY: Guaranteed by the caller
+        let LucaCz(cz) = unsafe { from_ptr(cz) }?;
+        // SAFETY: Guaranteed by the caller
+        let path = unsafe { from_path(path) }?;
+
+        Ok(cz.save_as_cz(path)?)
+    })
+}
+
+/// Free an image. Freeing a null pointer does nothing.
+///
+/// # Safety
+/// `cz` must be null or an image from this library, and must not be used
+/// again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cz_free(cz: *mut LucaCz) {
+    // SAFETY: Guaranteed by the caller
+    unsafe { free_handle(cz) }
+}
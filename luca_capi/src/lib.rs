@@ -0,0 +1,299 @@
+//! A C API for the [`cz`] and [`luca_pak`] crates.
+//!
+//! Every function returns a [`LucaStatus`] instead of panicking, and a
+//! message describing the last error on the calling thread can be read with
+//! [`luca_last_error()`]. Images and archives are passed around as opaque
+//! pointers which must be freed with [`cz_free()`] and [`pak_free()`], and
+//! data returned in a [`LucaBuffer`] must be freed with
+//! [`luca_buffer_free()`].
+//!
+//! The header for this API is kept in `include/luca_capi.h`, and is checked
+//! against the API by the `header` test, which writes it again when run with
+//! `UPDATE_HEADER=1`.
+//!
+//! Panics are caught and returned as [`LucaStatus::Panic`] only when the
+//! library is built with `panic = "unwind"`, like the default `release`
+//! profile. The workspace's `production` profile sets `panic = "abort"`, so
+//! with it any panic aborts the host process.
+
+mod archive;
+mod image;
+
+pub use crate::{archive::*, image::*};
+
+use cz::common::CzError;
+use luca_pak::PakError;
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr,
+};
+
+/// The result of a call to the API
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LucaStatus {
+    /// The call succeeded
+    Ok = 0,
+
+    /// A required pointer was null
+    NullPointer = 1,
+
+    /// An argument was invalid, like a path which is not UTF-8 or a bitmap
+    /// of the wrong size
+    InvalidArgument = 2,
+
+    /// A file could not be read or written
+    Io = 3,
+
+    /// The data is not a valid CZ image or PAK archive
+    InvalidData = 4,
+
+    /// An entry index is out of range
+    NotFound = 5,
+
+    /// An unexpected internal error, which is a bug in the library. Only
+    /// returned if the library is built to unwind on panic, otherwise the
+    /// process aborts.
+    Panic = 6,
+}
+
+/// Bytes allocated by the library, which must be freed with
+/// [`luca_buffer_free()`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct LucaBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl LucaBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+
+        Self { data, len }
+    }
+}
+
+/// Free a buffer returned by the library. Freeing a buffer with a null data
+/// pointer does nothing.
+///
+/// # Safety
+/// The buffer must have been returned by this library, and must not be used
+/// or freed again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn luca_buffer_free(buffer: LucaBuffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+
+    let slice = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+
+    // SAFETY: The buffer was created from a boxed slice by `from_vec`
+    drop(unsafe { Box::from_raw(slice) });
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Get a message describing the last error on this thread, or null if the
+/// last call succeeded. The message is valid until the next call into the
+/// library on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn luca_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|e| e.as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// A failed call, with the status to return and a message for
+/// [`luca_last_error()`]
+pub(crate) struct Failure(LucaStatus, String);
+
+impl Failure {
+    pub(crate) fn new(status: LucaStatus, message: impl ToString) -> Self {
+        Self(status, message.to_string())
+    }
+}
+
+impl From<CzError> for Failure {
+    fn from(err: CzError) -> Self {
+        let status = match err {
+            CzError::IoError(_) => LucaStatus::Io,
+            CzError::BitmapFormat | CzError::InvalidSize(..) | CzError::UnsupportedDepth(..) => {
+                LucaStatus::InvalidArgument
+            }
+            _ => LucaStatus::InvalidData,
+        };
+
+        Self::new(status, err)
+    }
+}
+
+impl From<PakError> for Failure {
+    fn from(err: PakError) -> Self {
+        let status = match err {
+            PakError::IoError(_) => LucaStatus::Io,
+            PakError::IndexError => LucaStatus::NotFound,
+            PakError::InvalidName(_)
+            | PakError::DuplicateName(_)
+            | PakError::DataTooLarge
+            | PakError::NoNames
+            | PakError::IdAddressed => LucaStatus::InvalidArgument,
+            _ => LucaStatus::InvalidData,
+        };
+
+        Self::new(status, err)
+    }
+}
+
+/// Run the body of an API function, catching any panic and recording the
+/// error message for [`luca_last_error()`]. Panics can only be caught if the
+/// library is built with `panic = "unwind"`.
+pub(crate) fn ffi_call<F: FnOnce() -> Result<(), Failure>>(f: F) -> LucaStatus {
+    let result = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown panic"));
+
+            Err(Failure::new(LucaStatus::Panic, message))
+        }
+    };
+
+    let (status, message) = match result {
+        Ok(()) => (LucaStatus::Ok, None),
+        Err(Failure(status, message)) => {
+            let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+            (status, Some(message))
+        }
+    };
+
+    LAST_ERROR.set(message);
+    status
+}
+
+/// Borrow a value from a pointer passed to the API
+///
+/// # Safety
+/// The pointer must be null, or valid for reads for the returned lifetime.
+pub(crate) unsafe fn from_ptr<'a, T>(value: *const T) -> Result<&'a T, Failure> {
+    // SAFETY: Guaranteed by the caller
+    let value = unsafe { value.as_ref() };
+
+    value.ok_or_else(|| Failure::new(LucaStatus::NullPointer, "Null pointer"))
+}
+
+/// Mutably borrow a value from a pointer passed to the API
+///
+/// # Safety
+/// The pointer must be null, or valid for reads and writes for the returned
+/// lifetime.
+pub(crate) unsafe fn from_mut_ptr<'a, T>(value: *mut T) -> Result<&'a mut T, Failure> {
+    // SAFETY: Guaranteed by the caller
+    let value = unsafe { value.as_mut() };
+
+    value.ok_or_else(|| Failure::new(LucaStatus::NullPointer, "Null pointer"))
+}
+
+/// Borrow a byte slice from a pointer and length passed to the API. A null
+/// pointer is allowed for an empty slice.
+///
+/// # Safety
+/// The pointer must be valid for reads of `len` bytes for the returned
+/// lifetime.
+pub(crate) unsafe fn from_bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Failure> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+
+    if data.is_null() {
+        return Err(Failure::new(LucaStatus::NullPointer, "Null data pointer"));
+    }
+
+    // SAFETY: Guaranteed by the caller
+    Ok(unsafe { std::slice::from_raw_parts(data, len) })
+}
+
+/// Borrow a path from a nul terminated UTF-8 string passed to the API
+///
+/// # Safety
+/// The pointer must be null, or point to a nul terminated string which is
+/// valid for the returned lifetime.
+pub(crate) unsafe fn from_path<'a>(path: *const c_char) -> Result<&'a Path, Failure> {
+    if path.is_null() {
+        return Err(Failure::new(LucaStatus::NullPointer, "Null path"));
+    }
+
+    // SAFETY: Guaranteed by the caller
+    let path = unsafe { CStr::from_ptr(path) };
+    match path.to_str() {
+        Ok(p) => Ok(Path::new(p)),
+        Err(_) => Err(Failure::new(
+            LucaStatus::InvalidArgument,
+            "Path is not valid UTF-8",
+        )),
+    }
+}
+
+/// Write a value to an output pointer passed to the API
+///
+/// # Safety
+/// The pointer must be null, or valid for writes.
+pub(crate) unsafe fn write_out<T>(out: *mut T, value: T) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(Failure::new(LucaStatus::NullPointer, "Null output pointer"));
+    }
+
+    // SAFETY: Guaranteed by the caller
+    unsafe { out.write(value) };
+
+    Ok(())
+}
+
+/// Give ownership of bytes to the caller through an output pointer
+///
+/// # Safety
+/// The pointer must be null, or valid for writes.
+pub(crate) unsafe fn write_buffer(out: *mut LucaBuffer, bytes: Vec<u8>) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(Failure::new(LucaStatus::NullPointer, "Null output pointer"));
+    }
+
+    // SAFETY: Guaranteed by the caller
+    unsafe { out.write(LucaBuffer::from_vec(bytes)) };
+
+    Ok(())
+}
+
+/// Give ownership of a handle to the caller through an output pointer
+///
+/// # Safety
+/// The pointer must be null, or valid for writes.
+pub(crate) unsafe fn write_handle<T>(out: *mut *mut T, value: T) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(Failure::new(LucaStatus::NullPointer, "Null output pointer"));
+    }
+
+    // SAFETY: Guaranteed by the caller
+    unsafe { out.write(Box::into_raw(Box::new(value))) };
+
+    Ok(())
+}
+
+/// Free a handle given to the caller by [`write_handle()`]
+///
+/// # Safety
+/// The pointer must be null, or have been given out by [`write_handle()`]
+/// and not freed already.
+pub(crate) unsafe fn free_handle<T>(handle: *mut T) {
+    if !handle.is_null() {
+        // SAFETY: Guaranteed by the caller
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
@@ -0,0 +1,73 @@
+//! Compile `smoke.c` against the shared library and run it
+
+#![cfg(target_os = "linux")]
+
+use std::{env, fs, path::PathBuf, process::Command};
+
+/// Build a PAK archive with two named entries, "a.txt" and "b.txt"
+fn test_pak() -> Vec<u8> {
+    const BLOCK_SIZE: u32 = 4;
+    let names: &[u8] = b"a.txt\0b.txt\0";
+
+    // Header, one word of unknown data, the entry locations and the names
+    let data_offset = 36 + 4 + 2 * 8 + names.len() as u32;
+    let first_block = data_offset / BLOCK_SIZE;
+
+    let mut pak = Vec::new();
+    for word in [data_offset, 2, 0, BLOCK_SIZE, 0, 0, 0, 0, 0b01000000000, 0] {
+        pak.extend_from_slice(&u32::to_le_bytes(word));
+    }
+    for (offset, length) in [(first_block, 5), (first_block + 2, 6)] {
+        pak.extend_from_slice(&u32::to_le_bytes(offset));
+        pak.extend_from_slice(&u32::to_le_bytes(length));
+    }
+    pak.extend_from_slice(names);
+    pak.extend_from_slice(b"hello\0\0\0world!\0\0");
+
+    pak
+}
+
+#[test]
+fn c_smoke_test() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    // Cargo builds the shared library next to the test executable, and copies
+    // it to the folder above for normal builds
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe
+        .ancestors()
+        .skip(1)
+        .take(2)
+        .find(|dir| dir.join("libluca_capi.so").exists())
+        .expect("Shared library was not built");
+
+    let out_dir = env::temp_dir().join(format!("luca_capi_smoke_{}", std::process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+    fs::write(out_dir.join("test.pak"), test_pak()).unwrap();
+
+    let binary = out_dir.join("smoke");
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| String::from("cc")))
+        .arg(crate_dir.join("tests/smoke.c"))
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lluca_capi")
+        .args(["-Wall", "-Werror", "-o"])
+        .arg(&binary)
+        .status()
+        .expect("Could not run the C compiler");
+    assert!(status.success(), "Could not compile smoke.c");
+
+    let output = Command::new(&binary).arg(&out_dir).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(fs::read(out_dir.join("a.txt")).unwrap(), b"hello");
+
+    fs::remove_dir_all(&out_dir).unwrap();
+}
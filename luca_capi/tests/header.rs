@@ -0,0 +1,27 @@
+//! Check that the committed C header matches the API. Run with
+//! `UPDATE_HEADER=1` to write the header again after changing the API.
+
+use std::{env, fs, path::PathBuf};
+
+#[test]
+fn header_is_current() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let header_path = crate_dir.join("include/luca_capi.h");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let mut header = Vec::new();
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("Could not generate the C header")
+        .write(&mut header);
+
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&header_path, header).unwrap();
+        return;
+    }
+
+    let committed = fs::read(&header_path).unwrap();
+    assert!(
+        committed == header,
+        "include/luca_capi.h is out of date, run `UPDATE_HEADER=1 cargo test -p luca_capi --test header`"
+    );
+}
@@ -0,0 +1,138 @@
+/*
+ * Smoke test for the C API, run by `c_smoke.rs`. Takes a folder containing
+ * `test.pak`, an archive with the entries "a.txt" = "hello" and
+ * "b.txt" = "world!", and writes its output files to the same folder.
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "luca_capi.h"
+
+#define CHECK(expr)                                                          \
+    do {                                                                     \
+        if (!(expr)) {                                                       \
+            const char *error = luca_last_error();                           \
+            fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__,      \
+                    __LINE__, #expr, error ? error : "no error");            \
+            return 1;                                                        \
+        }                                                                    \
+    } while (0)
+
+static void join(char *out, size_t size, const char *dir, const char *name) {
+    snprintf(out, size, "%s/%s", dir, name);
+}
+
+static int test_cz(const char *dir) {
+    uint8_t rgba[4 * 4 * 4];
+    for (size_t i = 0; i < sizeof(rgba); i++) {
+        rgba[i] = (uint8_t)(i * 7);
+    }
+
+    LucaCz *cz = NULL;
+    CHECK(cz_from_rgba(3, 4, 4, rgba, sizeof(rgba), &cz) == LUCA_STATUS_OK);
+
+    LucaBuffer encoded;
+    CHECK(cz_encode(cz, &encoded) == LUCA_STATUS_OK);
+    CHECK(encoded.len > 15 && memcmp(encoded.data, "CZ3", 3) == 0);
+
+    LucaCz *decoded = NULL;
+    CHECK(cz_decode(encoded.data, encoded.len, &decoded) == LUCA_STATUS_OK);
+    luca_buffer_free(encoded);
+
+    LucaCzInfo info;
+    CHECK(cz_info(decoded, &info) == LUCA_STATUS_OK);
+    CHECK(info.version == 3 && info.width == 4 && info.height == 4);
+
+    const uint8_t *pixels = NULL;
+    size_t len = 0;
+    CHECK(cz_rgba(decoded, &pixels, &len) == LUCA_STATUS_OK);
+    CHECK(len == sizeof(rgba) && memcmp(pixels, rgba, len) == 0);
+
+    char path[4096];
+    join(path, sizeof(path), dir, "test.cz3");
+    CHECK(cz_save(decoded, path) == LUCA_STATUS_OK);
+    cz_free(decoded);
+    cz_free(cz);
+
+    CHECK(cz_open(path, &cz) == LUCA_STATUS_OK);
+    CHECK(cz_rgba(cz, &pixels, &len) == LUCA_STATUS_OK);
+    CHECK(len == sizeof(rgba) && memcmp(pixels, rgba, len) == 0);
+
+    /* Errors are returned, not raised */
+    CHECK(cz_set_rgba(cz, rgba, 3) == LUCA_STATUS_INVALID_ARGUMENT);
+    CHECK(luca_last_error() != NULL);
+    cz_free(cz);
+
+    LucaCz *invalid = NULL;
+    CHECK(cz_decode((const uint8_t *)"not a cz", 8, &invalid) != LUCA_STATUS_OK);
+    CHECK(invalid == NULL);
+    CHECK(cz_info(NULL, &info) == LUCA_STATUS_NULL_POINTER);
+
+    return 0;
+}
+
+static int test_pak(const char *dir) {
+    char path[4096];
+    join(path, sizeof(path), dir, "test.pak");
+
+    LucaPak *pak = NULL;
+    CHECK(pak_open(path, &pak) == LUCA_STATUS_OK);
+
+    size_t count = 0;
+    CHECK(pak_entry_count(pak, &count) == LUCA_STATUS_OK);
+    CHECK(count == 2);
+
+    LucaPakEntry entry;
+    CHECK(pak_entry_info(pak, 1, &entry) == LUCA_STATUS_OK);
+    CHECK(entry.len == 6 && strcmp(entry.name, "b.txt") == 0);
+    CHECK(pak_entry_info(pak, 2, &entry) == LUCA_STATUS_NOT_FOUND);
+
+    const uint8_t *data = NULL;
+    size_t len = 0;
+    CHECK(pak_entry_data(pak, 0, &data, &len) == LUCA_STATUS_OK);
+    CHECK(len == 5 && memcmp(data, "hello", 5) == 0);
+
+    join(path, sizeof(path), dir, "a.txt");
+    CHECK(pak_extract(pak, 0, path) == LUCA_STATUS_OK);
+
+    const char *replacement = "a much longer replacement";
+    size_t replacement_len = strlen(replacement);
+    CHECK(pak_replace(pak, 0, (const uint8_t *)replacement, replacement_len) == LUCA_STATUS_OK);
+
+    join(path, sizeof(path), dir, "out.pak");
+    CHECK(pak_save(pak, path) == LUCA_STATUS_OK);
+    pak_free(pak);
+
+    CHECK(pak_open(path, &pak) == LUCA_STATUS_OK);
+    CHECK(pak_entry_data(pak, 0, &data, &len) == LUCA_STATUS_OK);
+    CHECK(len == replacement_len && memcmp(data, replacement, len) == 0);
+    CHECK(pak_entry_data(pak, 1, &data, &len) == LUCA_STATUS_OK);
+    CHECK(len == 6 && memcmp(data, "world!", 6) == 0);
+
+    LucaBuffer encoded;
+    CHECK(pak_encode(pak, &encoded) == LUCA_STATUS_OK);
+    pak_free(pak);
+
+    CHECK(pak_decode(encoded.data, encoded.len, &pak) == LUCA_STATUS_OK);
+    luca_buffer_free(encoded);
+    CHECK(pak_entry_count(pak, &count) == LUCA_STATUS_OK);
+    CHECK(count == 2);
+    pak_free(pak);
+
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <folder>\n", argv[0]);
+        return 2;
+    }
+
+    if (test_cz(argv[1]) != 0 || test_pak(argv[1]) != 0) {
+        return 1;
+    }
+
+    printf("C API smoke test passed\n");
+    return 0;
+}
@@ -0,0 +1,19 @@
+//! Decode arbitrary bytes as a PAK file, and encode it again if it decodes
+
+#![no_main]
+
+use std::{io::Cursor, path::PathBuf};
+
+use libfuzzer_sys::fuzz_target;
+use luca_pak::{Pak, PakLimits};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = PakLimits {
+        entry_limit: 1024,
+        ..Default::default()
+    };
+
+    if let Ok(pak) = Pak::decode(&mut Cursor::new(data), PathBuf::new(), limits) {
+        let _ = pak.encode(&mut Vec::new());
+    }
+});
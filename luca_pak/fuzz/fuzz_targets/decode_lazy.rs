@@ -0,0 +1,24 @@
+//! Lazily decode arbitrary bytes as a PAK file, then read every entry and
+//! encode it again if it decodes
+
+#![no_main]
+
+use std::{io::Cursor, path::PathBuf};
+
+use libfuzzer_sys::fuzz_target;
+use luca_pak::{Pak, PakLimits};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = PakLimits {
+        entry_limit: 1024,
+        ..Default::default()
+    };
+
+    if let Ok(pak) = Pak::decode_lazy(Cursor::new(data.to_vec()), PathBuf::new(), limits) {
+        for entry in pak.entries() {
+            let _ = entry.file_type();
+            let _ = entry.as_bytes();
+        }
+        let _ = pak.encode(&mut Vec::new());
+    }
+});
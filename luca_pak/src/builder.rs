@@ -0,0 +1,157 @@
+use std::{collections::HashSet, path::PathBuf, sync::OnceLock};
+
+use crate::{
+    Pak, PakError,
+    entry::Entry,
+    header::{Header, PakFlags},
+};
+
+/// Flags for a PAK with entry names and one word of unknown data after the
+/// header
+const DEFAULT_FLAGS: u32 = 0b01000000000;
+
+/// Creates a new PAK file from a list of named entries.
+///
+/// ```
+/// use luca_pak::builder::PakBuilder;
+///
+/// let pak = PakBuilder::new(2048)
+///     .with_subdirectory("patch")
+///     .with_entry("script.dat", vec![0u8; 100])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(pak.header().data_offset(), 2048);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PakBuilder {
+    block_size: u32,
+    id_start: u32,
+    flags: PakFlags,
+    subdirectory: Option<String>,
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl PakBuilder {
+    /// Create a builder for a PAK with entries aligned to `block_size` bytes,
+    /// with entry IDs starting at 0 and names stored in the PAK.
+    pub fn new(block_size: u32) -> Self {
+        Self {
+            block_size,
+            id_start: 0,
+            flags: PakFlags(DEFAULT_FLAGS),
+            subdirectory: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Set the ID of the first entry, which the IDs of all others follow
+    pub fn with_id_start(mut self, id_start: u32) -> Self {
+        self.id_start = id_start;
+        self
+    }
+
+    /// Set the flags of the PAK. The names of the entries and the
+    /// subdirectory are only written if [`PakFlags::has_names()`] is true.
+    /// Any unknown data the flags call for is filled with zeros.
+    pub fn with_flags(mut self, flags: PakFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the name of the subdirectory stored before the entry names
+    pub fn with_subdirectory(mut self, subdirectory: &str) -> Self {
+        self.subdirectory = Some(subdirectory.to_string());
+        self
+    }
+
+    /// Add an entry to the end of the PAK
+    pub fn with_entry(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.add_entry(name, data);
+        self
+    }
+
+    /// Add an entry to the end of the PAK
+    pub fn add_entry(&mut self, name: &str, data: Vec<u8>) {
+        self.entries.push((name.to_string(), data));
+    }
+
+    /// Build the PAK, laying out the header, the entry tables and the entry
+    /// data aligned to the block size
+    pub fn build(self) -> Result<Pak, PakError> {
+        if self.block_size == 0 {
+            return Err(PakError::HeaderError);
+        }
+
+        let entry_count = u32::try_from(self.entries.len()).map_err(|_| PakError::DataTooLarge)?;
+        if self.flags.has_names() {
+            if let Some(subdir) = &self.subdirectory {
+                check_name(subdir)?;
+            }
+            let mut names = HashSet::new();
+            for (name, _) in &self.entries {
+                check_name(name)?;
+                if !names.insert(name) {
+                    return Err(PakError::DuplicateName(name.clone()));
+                }
+            }
+        }
+
+        let entries = self
+            .entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, data))| {
+                let length = u32::try_from(data.len()).map_err(|_| PakError::DataTooLarge)?;
+
+                Ok(Entry {
+                    index: i,
+                    offset: 0,
+                    length,
+                    unknown1: self.flags.has_unknown_data1().then_some([0u8; 12]),
+                    name: self.flags.has_names().then_some(name),
+                    id: self
+                        .id_start
+                        .checked_add(i as u32)
+                        .ok_or(PakError::DataTooLarge)?,
+                    data: OnceLock::from(data),
+                    source: None,
+                    entry_type: OnceLock::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, PakError>>()?;
+
+        let unknown_pre_data = vec![0; self.flags.extra_pre_count()];
+        let mut pak = Pak {
+            subdirectory: self.subdirectory.filter(|_| self.flags.has_names()),
+            path: PathBuf::new(),
+            header: Header {
+                data_offset: 0,
+                entry_count,
+                id_start: self.id_start,
+                block_size: self.block_size,
+                subdir_offset: 0,
+                unknown2: 0,
+                unknown3: 0,
+                unknown4: 0,
+                flags: self.flags,
+            },
+            unknown_pre_data,
+            unknown_post_header: Vec::new(),
+            entries,
+        };
+        pak.fit_header()?;
+        pak.pack_entries(0)?;
+
+        Ok(pak)
+    }
+}
+
+/// Check that a name can be stored in the PAK name table
+pub(crate) fn check_name(name: &str) -> Result<(), PakError> {
+    if name.is_empty() || name.contains('\0') {
+        return Err(PakError::InvalidName(name.to_string()));
+    }
+
+    Ok(())
+}
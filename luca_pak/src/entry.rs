@@ -1,9 +1,17 @@
 use std::{
-    error::Error, fmt::Display, fs::File, io::{BufWriter, Write}, path::Path
+    borrow::Cow,
+    error::Error,
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::OnceLock,
 };
 
+use crate::{PakError, source::EntrySource};
+
 /// A single file entry in a PAK file
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Entry {
     pub(super) index: usize,
 
@@ -23,10 +31,46 @@ pub struct Entry {
     /// The ID of the entry, effectively an index
     pub(super) id: u32,
 
-    /// The actual data which makes up the entry
-    pub(super) data: Vec<u8>,
+    /// The actual data which makes up the entry, which is empty until it is
+    /// first used if the PAK was decoded lazily
+    pub(super) data: OnceLock<Vec<u8>>,
+
+    /// Where to read the data from if it has not been read yet
+    pub(super) source: Option<EntrySource>,
+
+    /// The type of the data, once it has been found by [`Entry::file_type()`]
+    pub(super) entry_type: OnceLock<EntryType>,
+}
+
+/// Entries are equal if they are stored the same way and hold the same data.
+/// Data which has not been read from a lazily decoded PAK is read to compare
+/// it, unless both entries come from the same place in the same file, and
+/// entries whose data can't be read are not equal to anything but themselves.
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        if self.index != other.index
+            || self.offset != other.offset
+            || self.length != other.length
+            || self.unknown1 != other.unknown1
+            || self.name != other.name
+            || self.id != other.id
+        {
+            return false;
+        }
+
+        if !self.is_loaded() && !other.is_loaded() && self.source == other.source {
+            return true;
+        }
+
+        match (self.as_bytes(), other.as_bytes()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
+impl Eq for Entry {}
+
 impl Entry {
     /// Get the name of the [`Entry`]
     pub fn name(&self) -> &Option<String> {
@@ -45,7 +89,7 @@ impl Entry {
     pub fn save<P: ?Sized + AsRef<Path>>(&self, path: &P) -> Result<(), Box<dyn Error>> {
         let mut out_file = BufWriter::new(File::create(path)?);
 
-        out_file.write_all(&self.data)?;
+        self.write_into(&mut out_file)?;
         out_file.flush()?;
 
         Ok(())
@@ -59,55 +103,87 @@ impl Entry {
         self.len() == 0
     }
 
-    /// Get the raw byte data of an [`Entry`]
-    pub fn as_bytes(&self) -> &Vec<u8> {
-        &self.data
+    /// Get the raw byte data of an [`Entry`]. If the PAK was decoded lazily,
+    /// the data is read from the PAK file the first time this is called.
+    pub fn as_bytes(&self) -> Result<&[u8], PakError> {
+        if let Some(data) = self.data.get() {
+            return Ok(data);
+        }
+
+        let data = match &self.source {
+            Some(source) => source
+                .read(self.len())
+                .map_err(|e| PakError::EntryDataError(self.index, source.position(), e))?,
+            None => Vec::new(),
+        };
+
+        Ok(self.data.get_or_init(|| data))
     }
 
-    /// Get the byte data of an entry, but fixed to be compatible with normal things
-    pub fn cloned_bytes_fixed(&self) -> Vec<u8> {
-        match self.file_type() {
-            EntryType::OGGPAK => {
-                self.data[15..].to_vec()
-            },
-            _ => self.data.clone()
+    /// Returns true if the data of the [`Entry`] is in memory
+    pub fn is_loaded(&self) -> bool {
+        self.data.get().is_some() || self.source.is_none()
+    }
+
+    /// Write the data of an [`Entry`] into anything that implements
+    /// [`Write`]. Data which has not been read from a lazily decoded PAK is
+    /// copied straight from the PAK file without being kept in memory.
+    pub fn write_into<T: Write + ?Sized>(&self, output: &mut T) -> Result<(), PakError> {
+        match (self.data.get(), &self.source) {
+            (None, Some(source)) => source
+                .copy_to(self.length as u64, output)
+                .map_err(|e| PakError::EntryDataError(self.index, source.position(), e))?,
+            (data, _) => output.write_all(data.map_or(&[], |d| d.as_slice()))?,
         }
+
+        Ok(())
     }
 
+    /// Get the byte data of an entry, but fixed to be compatible with normal things
+    pub fn cloned_bytes_fixed(&self) -> Result<Vec<u8>, PakError> {
+        let data = self.as_bytes()?;
+
+        Ok(match self.file_type()? {
+            EntryType::OGGPAK => data.get(15..).unwrap_or_default().to_vec(),
+            _ => data.to_vec(),
+        })
+    }
+
+    /// Get the first few bytes of the data, without reading the rest of it
+    /// from a lazily decoded PAK
+    fn head(&self) -> Result<Cow<'_, [u8]>, PakError> {
+        Ok(match (self.data.get(), &self.source) {
+            (Some(data), _) => Cow::Borrowed(data),
+            (None, Some(source)) => Cow::Owned(
+                source
+                    .read(self.len().min(16))
+                    .map_err(|e| PakError::EntryDataError(self.index, source.position(), e))?,
+            ),
+            (None, None) => Cow::Borrowed(&[]),
+        })
+    }
+
+    /// Get the name of the entry with the extension for its type. If the type
+    /// of a lazily decoded entry can't be read, the name has no extension.
     pub fn display_name(&self) -> String {
         let mut name = self.name().clone().unwrap_or(self.id().to_string());
-        let entry_type = self.file_type();
-        name.push_str(entry_type.extension());
+        if let Ok(entry_type) = self.file_type() {
+            name.push_str(entry_type.extension());
+        }
 
         name
     }
 
-    pub fn file_type(&self) -> EntryType {
-        if self.data.is_empty() {
-            return EntryType::Unknown
+    /// Get the type of the entry from the first few bytes of its data. If the
+    /// PAK was decoded lazily, only those bytes are read, and only the first
+    /// time this is called.
+    pub fn file_type(&self) -> Result<EntryType, PakError> {
+        if let Some(entry_type) = self.entry_type.get() {
+            return Ok(*entry_type);
         }
 
-        if self.len() >= 3 && self.data[0..2] == [b'C', b'Z'] {
-            match self.data[2] {
-                b'0' => EntryType::CZ0,
-                b'1' => EntryType::CZ1,
-                b'2' => EntryType::CZ2,
-                b'3' => EntryType::CZ3,
-                b'4' => EntryType::CZ4,
-                b'5' => EntryType::CZ5,
-                _ => EntryType::Unknown,
-            }
-        } else if self.len() >= 4 && self.data[0..3] == [b'M', b'V', b'T'] {
-            EntryType::MVT
-        } else if self.len() >= 5 && self.data[0..4] == [b'R', b'I', b'F', b'F'] {
-            EntryType::WAV
-        } else if self.len() >= 5 && self.data[0..4] == [b'O', b'g', b'g', b'S'] {
-            EntryType::OGG
-        } else if self.len() >= 7 && self.data[0..6] == [b'O', b'G', b'G', b'P', b'A', b'K'] {
-            EntryType::OGGPAK
-        } else {
-            EntryType::Unknown
-        }
+        let entry_type = EntryType::from_head(&self.head()?);
+        Ok(*self.entry_type.get_or_init(|| entry_type))
     }
 }
 
@@ -157,6 +233,35 @@ impl Display for EntryType {
 }
 
 impl EntryType {
+    /// Find the type of some data from its first few bytes
+    fn from_head(data: &[u8]) -> Self {
+        if data.is_empty() {
+            return EntryType::Unknown;
+        }
+
+        if data.len() >= 3 && data[0..2] == [b'C', b'Z'] {
+            match data[2] {
+                b'0' => EntryType::CZ0,
+                b'1' => EntryType::CZ1,
+                b'2' => EntryType::CZ2,
+                b'3' => EntryType::CZ3,
+                b'4' => EntryType::CZ4,
+                b'5' => EntryType::CZ5,
+                _ => EntryType::Unknown,
+            }
+        } else if data.len() >= 4 && data[0..3] == [b'M', b'V', b'T'] {
+            EntryType::MVT
+        } else if data.len() >= 5 && data[0..4] == [b'R', b'I', b'F', b'F'] {
+            EntryType::WAV
+        } else if data.len() >= 5 && data[0..4] == [b'O', b'g', b'g', b'S'] {
+            EntryType::OGG
+        } else if data.len() >= 7 && data[0..6] == [b'O', b'G', b'G', b'P', b'A', b'K'] {
+            EntryType::OGGPAK
+        } else {
+            EntryType::Unknown
+        }
+    }
+
     /// Get the file extension for the file
     pub fn extension(&self) -> &'static str {
         match self {
@@ -1,7 +1,9 @@
+pub mod builder;
 pub mod entry;
 pub mod header;
+mod source;
 
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 use header::Header;
 use log::{debug, info};
 use std::{
@@ -9,10 +11,11 @@ use std::{
     fs::File,
     io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 use thiserror::Error;
 
-use crate::{entry::Entry, header::PakFlags};
+use crate::{entry::Entry, header::PakFlags, source::EntrySource};
 
 /// An error associated with a PAK file
 #[derive(Error, Debug)]
@@ -29,8 +32,32 @@ pub enum PakError {
     #[error("Malformed header information")]
     HeaderError,
 
+    #[error("Could not read the location of entry {0} at offset {1:#x}")]
+    EntryTableError(usize, u64, #[source] io::Error),
+
+    #[error("Could not read the data of entry {0} at offset {1:#x}")]
+    EntryDataError(usize, u64, #[source] io::Error),
+
+    #[error("Entry {0} is {1} bytes long, which exceeds the limit of {2}")]
+    EntrySizeLimit(usize, u32, usize),
+
     #[error("Index not found")]
     IndexError,
+
+    #[error("Name {0:?} is empty or contains a nul character")]
+    InvalidName(String),
+
+    #[error("An entry named {0:?} already exists")]
+    DuplicateName(String),
+
+    #[error("PAK data is too large to be stored")]
+    DataTooLarge,
+
+    #[error("PAK does not store names")]
+    NoNames,
+
+    #[error("Entries in a PAK without names are found by ID, which this would change")]
+    IdAddressed,
 }
 
 /// A full PAK file with a header and its contents
@@ -56,6 +83,15 @@ struct EntryLocation {
     length: u32,
 }
 
+impl EntryLocation {
+    fn read<T: Read>(input: &mut T) -> Result<Self, io::Error> {
+        Ok(Self {
+            offset: input.read_u32::<LE>()?,
+            length: input.read_u32::<LE>()?,
+        })
+    }
+}
+
 pub struct PakLimits {
     pub entry_limit: usize,
     pub size_limit: usize,
@@ -78,14 +114,69 @@ impl Pak {
         Pak::decode(&mut file, path.as_ref().to_path_buf(), PakLimits::default())
     }
 
+    /// Open a PAK file from a path without reading the entry data, which is
+    /// read from the file when it is used.
+    ///
+    /// Read more in [`Pak::decode_lazy()`]
+    pub fn open_lazy<P: ?Sized + AsRef<Path>>(path: &P) -> Result<Self, PakError> {
+        let file = BufReader::new(File::open(path)?);
+
+        Pak::decode_lazy(file, path.as_ref().to_path_buf(), PakLimits::default())
+    }
+
     /// Decode a PAK file from a byte stream.
     pub fn decode<T: Seek + Read>(
         input: &mut T,
         path: PathBuf,
         limits: PakLimits,
     ) -> Result<Self, PakError> {
-        info!("Reading pak from {:?}", path);
         let mut input = BufReader::new(input);
+        let mut pak = Self::decode_headers(&mut input, path, limits)?;
+
+        // Read all entry data
+        for entry in &mut pak.entries {
+            debug!("Seeking to block {}", entry.offset);
+            let position = entry.offset as u64 * pak.header.block_size() as u64;
+            let data = read_data(&mut input, position, entry.length)
+                .map_err(|e| PakError::EntryDataError(entry.index, position, e))?;
+
+            entry.data = OnceLock::from(data);
+        }
+
+        Ok(pak)
+    }
+
+    /// Decode a PAK file from a byte stream, keeping the stream to read the
+    /// entry data from when it is used instead of reading it all up front.
+    /// This keeps memory use low for large archives, such as those holding
+    /// voice lines.
+    ///
+    /// Entries are read by [`Entry::as_bytes()`], and [`Pak::encode()`] copies
+    /// any entries which have not been read or replaced straight from the
+    /// stream.
+    pub fn decode_lazy<T: Seek + Read + Send + 'static>(
+        mut input: T,
+        path: PathBuf,
+        limits: PakLimits,
+    ) -> Result<Self, PakError> {
+        let mut pak = Self::decode_headers(&mut BufReader::new(&mut input), path, limits)?;
+
+        let reader = EntrySource::shared(input);
+        for entry in &mut pak.entries {
+            let position = entry.offset as u64 * pak.header.block_size() as u64;
+            entry.source = Some(EntrySource::new(&reader, position));
+        }
+
+        Ok(pak)
+    }
+
+    /// Decode everything in a PAK file except for the entry data
+    fn decode_headers<T: Seek + Read>(
+        input: &mut BufReader<T>,
+        path: PathBuf,
+        limits: PakLimits,
+    ) -> Result<Self, PakError> {
+        info!("Reading pak from {:?}", path);
 
         // Read in all the header bytes
         debug!("READING: header");
@@ -103,6 +194,12 @@ impl Pak {
 
         if header.entry_count >= limits.entry_limit as u32 {
             return Err(PakError::EntryLimit(header.entry_count, limits.entry_limit));
+        } else if header.block_size == 0 {
+            log::error!("Block size is 0");
+            return Err(PakError::HeaderError);
+        } else if header.id_start.checked_add(header.entry_count).is_none() {
+            log::error!("Entry IDs exceed the maximum ID");
+            return Err(PakError::HeaderError);
         }
         info!("{} entries detected", header.entry_count);
         debug!("Block size is {} bytes", header.block_size);
@@ -110,7 +207,10 @@ impl Pak {
 
         let mut unknown_pre_data = Vec::new();
         if header.flags.extra_pre_count() > 0 {
-            debug!("Reading {} bytes of unknown data extra", header.flags.extra_pre_count() * 4);
+            debug!(
+                "Reading {} bytes of unknown data extra",
+                header.flags.extra_pre_count() * 4
+            );
             for _ in 0..header.flags.extra_pre_count() {
                 unknown_pre_data.push(input.read_u32::<LE>()?);
             }
@@ -125,10 +225,19 @@ impl Pak {
         // TODO: I think a flag controls this
         debug!("READING: offsets");
         let mut offsets = Vec::new();
-        for _ in 0..header.entry_count() {
-            let offset = input.read_u32::<LE>().unwrap();
-            let length = input.read_u32::<LE>().unwrap();
-            offsets.push(EntryLocation { offset, length });
+        for i in 0..header.entry_count() as usize {
+            let position = input.stream_position()?;
+            let location = EntryLocation::read(input)
+                .map_err(|e| PakError::EntryTableError(i, position, e))?;
+
+            if location.length as usize > limits.size_limit {
+                return Err(PakError::EntrySizeLimit(
+                    i,
+                    location.length,
+                    limits.size_limit,
+                ));
+            }
+            offsets.push(location);
         }
 
         // Read all unknown_data1
@@ -150,20 +259,23 @@ impl Pak {
         if header.flags.has_names() {
             debug!("READING: file_names");
             if header.subdir_offset != 0 {
-                subdirectory = Some(read_cstring(&mut input)?);
+                subdirectory = Some(read_cstring(input)?);
             }
             file_names = Some(Vec::new());
             for _ in 0..header.entry_count() {
-                let strbuf = read_cstring(&mut input)?;
+                let strbuf = read_cstring(input)?;
                 file_names.as_mut().unwrap().push(strbuf.clone());
             }
         }
 
-        let unknown_post_header_size = header.data_offset() as u64 - input.stream_position()?;
-        let mut unknown_post_header = vec![0u8; unknown_post_header_size as usize];
-        input.read_exact(&mut unknown_post_header)?;
+        let Some(unknown_post_header_size) =
+            (header.data_offset() as u64).checked_sub(input.stream_position()?)
+        else {
+            log::error!("Entry tables exceeded first data block");
+            return Err(PakError::HeaderError);
+        };
+        let unknown_post_header = read_exact_len(input, unknown_post_header_size)?;
 
-        // Read all entry data
         debug!("Creating entry list");
         let mut entries: Vec<Entry> = Vec::new();
         for (i, offset_info) in offsets
@@ -171,16 +283,6 @@ impl Pak {
             .take(header.entry_count() as usize)
             .enumerate()
         {
-            debug!("Seeking to block {}", offset_info.offset);
-            // Seek to and read the entry data
-            input
-                .seek(SeekFrom::Start(
-                    offset_info.offset as u64 * header.block_size() as u64,
-                ))
-                .unwrap();
-            let mut data = vec![0u8; offset_info.length as usize];
-            input.read_exact(&mut data).unwrap();
-
             let name = if let Some(file_names) = &file_names {
                 file_names.get(i).cloned()
             } else {
@@ -199,7 +301,9 @@ impl Pak {
                 offset: offset_info.offset,
                 length: offset_info.length,
                 unknown1,
-                data,
+                data: OnceLock::new(),
+                source: None,
+                entry_type: OnceLock::new(),
                 name,
                 id: header.id_start + i as u32,
             };
@@ -218,10 +322,22 @@ impl Pak {
     }
 
     /// Convenience method to save the PAK to a file.
+    ///
+    /// If the PAK was decoded lazily and is saved over the file it was opened
+    /// from, all of the entry data is read into memory first.
     pub fn save<P: ?Sized + AsRef<Path>>(&self, path: &P) -> Result<(), PakError> {
-        let mut output = BufWriter::new(File::create(path).unwrap());
+        if let (Ok(a), Ok(b)) = (self.path.canonicalize(), path.as_ref().canonicalize())
+            && a == b
+        {
+            for entry in &self.entries {
+                entry.as_bytes()?;
+            }
+        }
+
+        let mut output = BufWriter::new(File::create(path)?);
 
-        self.encode(&mut output).unwrap();
+        self.encode(&mut output)?;
+        output.flush()?;
 
         Ok(())
     }
@@ -270,14 +386,11 @@ impl Pak {
         for entry in self.entries() {
             //let block_size = entry.data.len().div_ceil(self.header().block_size as usize);
             let mut remainder = self.header().block_size as usize
-                - entry
-                    .data
-                    .len()
-                    .rem_euclid(self.header().block_size as usize);
+                - entry.len().rem_euclid(self.header().block_size as usize);
             if remainder == self.header().block_size as usize {
                 remainder = 0;
             }
-            output.write_all(&entry.data)?;
+            entry.write_into(&mut output)?;
             output.write_all(&vec![0u8; remainder])?;
 
             //println!("entry len {}", entry.data.len());
@@ -311,8 +424,11 @@ impl Pak {
         }
 
         // Replace the entry data
-        replaced_entry.data = replacement_bytes.to_vec();
-        replaced_entry.length = replaced_entry.data.len() as u32;
+        replaced_entry.length =
+            u32::try_from(replacement_bytes.len()).map_err(|_| PakError::DataTooLarge)?;
+        replaced_entry.data = OnceLock::from(replacement_bytes.to_vec());
+        replaced_entry.source = None;
+        replaced_entry.entry_type = OnceLock::new();
 
         // If the offset is 0, ensure we move it to the correct position
         if replaced_entry.length == 0 {
@@ -326,21 +442,35 @@ impl Pak {
             }
 
             if prev_offset == 0 {
-                prev_offset = self.header().data_offset.div_euclid(self.header().block_size);
+                prev_offset = self
+                    .header()
+                    .data_offset
+                    .div_euclid(self.header().block_size);
             }
 
             replaced_entry.offset = prev_offset;
         }
 
         // Get the offset of the next entry based on the current one
-        let mut next_offset = replaced_entry.offset + replaced_entry.length.div_ceil(block_size);
+        let mut next_offset = replaced_entry
+            .offset
+            .checked_add(replaced_entry.length.div_ceil(block_size))
+            .ok_or(PakError::DataTooLarge)?;
 
         // Update the position of all subsequent entries, but not those which are 0
         let mut i = 0;
-        for entry in self.entries.iter_mut().skip(index + 1).filter(|e| e.offset != 0) {
+        for entry in self
+            .entries
+            .iter_mut()
+            .skip(index + 1)
+            .filter(|e| e.offset != 0)
+        {
             entry.offset = next_offset;
 
-            next_offset = entry.offset + entry.length.div_ceil(block_size);
+            next_offset = entry
+                .offset
+                .checked_add(entry.length.div_ceil(block_size))
+                .ok_or(PakError::DataTooLarge)?;
             i += 1;
         }
 
@@ -384,6 +514,286 @@ impl Pak {
         Ok(())
     }
 
+    /// Add an entry to the end of the PAK. The name is only stored if the
+    /// PAK has names, in which case it must be unique.
+    ///
+    /// The header grows to fit the new entry, which moves the data of every
+    /// entry if there is not enough room left before it.
+    pub fn push_entry(&mut self, name: &str, data: &[u8]) -> Result<(), PakError> {
+        self.insert_entry(self.entries.len(), name, data)
+    }
+
+    /// Insert an entry at an index, moving all entries after it along by one.
+    ///
+    /// The IDs of the entries after the new one change, so this is refused
+    /// for PAKs without names, which are only addressed by ID, unless the
+    /// entry goes at the end. Read more in [`Pak::push_entry()`]
+    pub fn insert_entry(&mut self, index: usize, name: &str, data: &[u8]) -> Result<(), PakError> {
+        if index > self.entries.len() {
+            return Err(PakError::IndexError);
+        } else if index != self.entries.len() && !self.header.flags().has_names() {
+            return Err(PakError::IdAddressed);
+        }
+
+        let name = if self.header.flags().has_names() {
+            builder::check_name(name)?;
+            if self.contains_name(name) {
+                return Err(PakError::DuplicateName(name.to_string()));
+            }
+            Some(name.to_string())
+        } else {
+            None
+        };
+
+        let entry_count = self
+            .header
+            .entry_count
+            .checked_add(1)
+            .ok_or(PakError::DataTooLarge)?;
+        let length = u32::try_from(data.len()).map_err(|_| PakError::DataTooLarge)?;
+        if self.header.id_start.checked_add(entry_count - 1).is_none() {
+            return Err(PakError::DataTooLarge);
+        }
+
+        info!("Inserting entry {}", index);
+        self.entries.insert(
+            index,
+            Entry {
+                index,
+                offset: 0,
+                length,
+                unknown1: self.header.flags().has_unknown_data1().then_some([0u8; 12]),
+                name,
+                id: 0,
+                data: OnceLock::from(data.to_vec()),
+                source: None,
+                entry_type: OnceLock::new(),
+            },
+        );
+        self.header.entry_count = entry_count;
+        self.renumber_entries(index);
+
+        if let Err(e) = self.update_layout(index) {
+            self.entries.remove(index);
+            self.header.entry_count -= 1;
+            self.renumber_entries(index);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Remove an entry, moving the data of all entries after it back to fill
+    /// the space it leaves.
+    ///
+    /// The IDs of the entries after the removed one change, so this is
+    /// refused for PAKs without names, which are only addressed by ID, unless
+    /// the entry is the last one.
+    pub fn remove_entry(&mut self, index: usize) -> Result<Entry, PakError> {
+        if index >= self.entries.len() {
+            return Err(PakError::IndexError);
+        } else if index != self.entries.len() - 1 && !self.header.flags().has_names() {
+            return Err(PakError::IdAddressed);
+        }
+
+        info!("Removing entry {}", index);
+        let entry = self.entries.remove(index);
+        self.header.entry_count -= 1;
+        self.renumber_entries(index);
+
+        if let Err(e) = self.update_layout(index) {
+            self.entries.insert(index, entry);
+            self.header.entry_count += 1;
+            self.renumber_entries(index);
+            return Err(e);
+        }
+
+        Ok(entry)
+    }
+
+    /// Rename an entry. The new name must be unique within the PAK.
+    ///
+    /// The header grows to fit a longer name, which moves the data of every
+    /// entry if there is not enough room left before it.
+    pub fn rename(&mut self, index: usize, new_name: &str) -> Result<(), PakError> {
+        if !self.header.flags().has_names() {
+            return Err(PakError::NoNames);
+        } else if index >= self.entries.len() {
+            return Err(PakError::IndexError);
+        }
+
+        builder::check_name(new_name)?;
+        if self
+            .entries
+            .iter()
+            .any(|e| e.index != index && e.name.as_deref() == Some(new_name))
+        {
+            return Err(PakError::DuplicateName(new_name.to_string()));
+        }
+
+        info!("Renaming entry {} to {}", index, new_name);
+        let old_name = self.entries[index].name.replace(new_name.to_string());
+
+        if let Err(e) = self.update_layout(self.entries.len()) {
+            self.entries[index].name = old_name;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Get the name of the subdirectory stored before the entry names
+    pub fn subdirectory(&self) -> &Option<String> {
+        &self.subdirectory
+    }
+
+    /// Set or remove the name of the subdirectory stored before the entry
+    /// names.
+    ///
+    /// Read more in [`Pak::rename()`]
+    pub fn set_subdirectory(&mut self, subdirectory: Option<&str>) -> Result<(), PakError> {
+        if !self.header.flags().has_names() {
+            return Err(PakError::NoNames);
+        }
+
+        if let Some(subdir) = subdirectory {
+            builder::check_name(subdir)?;
+        }
+
+        let old_subdirectory =
+            std::mem::replace(&mut self.subdirectory, subdirectory.map(|s| s.to_string()));
+
+        if let Err(e) = self.update_layout(self.entries.len()) {
+            self.subdirectory = old_subdirectory;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Update the index and ID of every entry from `start` onwards to match
+    /// their position
+    fn renumber_entries(&mut self, start: usize) {
+        for (i, entry) in self.entries.iter_mut().enumerate().skip(start) {
+            entry.index = i;
+            entry.id = self.header.id_start + i as u32;
+        }
+    }
+
+    /// Fit the header and pack the entries from `start` onwards, putting the
+    /// layout back as it was if either fails so the PAK is left unchanged.
+    /// Entries are only moved by the header growing if `start` is past the
+    /// last entry.
+    fn update_layout(&mut self, start: usize) -> Result<(), PakError> {
+        let header = self.header.clone();
+        let unknown_post_header = self.unknown_post_header.clone();
+        let offsets: Vec<u32> = self.entries.iter().map(|e| e.offset).collect();
+
+        let result = self.fit_header().and_then(|_| self.pack_entries(start));
+        if result.is_err() {
+            self.header = header;
+            self.unknown_post_header = unknown_post_header;
+            for (entry, offset) in self.entries.iter_mut().zip(offsets) {
+                entry.offset = offset;
+            }
+        }
+
+        result
+    }
+
+    /// Length of the header and entry tables, which the entry data follows
+    fn header_len(&self) -> u64 {
+        let entry_count = self.entries.len() as u64;
+
+        let mut length = 36 + self.unknown_pre_data.len() as u64 * 4 + entry_count * 8;
+        if self.header.flags().has_unknown_data1() {
+            length += entry_count * 12;
+        }
+        if self.header.flags().has_names() {
+            length += self.subdirectory.as_ref().map_or(0, |s| s.len() as u64 + 1);
+            length += self
+                .entries
+                .iter()
+                .map(|e| e.name.as_ref().map_or(0, |n| n.len() as u64 + 1))
+                .sum::<u64>();
+        }
+
+        length
+    }
+
+    /// Make room for the header and entry tables before the entry data.
+    ///
+    /// If the tables grew past the start of the data, the data is moved to
+    /// the next block after them, otherwise the space left is filled with
+    /// padding so the data stays where it is.
+    fn fit_header(&mut self) -> Result<(), PakError> {
+        let block_size = self.header.block_size as u64;
+        let header_len = self.header_len();
+        let data_offset = self.header.data_offset as u64;
+
+        if header_len <= data_offset {
+            self.unknown_post_header
+                .resize((data_offset - header_len) as usize, 0);
+        } else {
+            let new_data_offset = header_len.next_multiple_of(block_size);
+            let shift = (new_data_offset / block_size - data_offset / block_size) as u32;
+            debug!("Moving entry data forward by {} blocks", shift);
+
+            self.header.data_offset =
+                u32::try_from(new_data_offset).map_err(|_| PakError::DataTooLarge)?;
+            self.unknown_post_header = vec![0u8; (new_data_offset - header_len) as usize];
+
+            for entry in self.entries.iter_mut().filter(|e| e.offset != 0) {
+                entry.offset = entry
+                    .offset
+                    .checked_add(shift)
+                    .ok_or(PakError::DataTooLarge)?;
+            }
+        }
+
+        // The subdirectory name comes right after the entry tables
+        self.header.subdir_offset = if self.subdirectory.is_some() {
+            let mut offset = 36 + self.unknown_pre_data.len() as u64 * 4;
+            offset += self.entries.len() as u64 * 8;
+            if self.header.flags().has_unknown_data1() {
+                offset += self.entries.len() as u64 * 12;
+            }
+            offset as u32
+        } else {
+            0
+        };
+
+        Ok(())
+    }
+
+    /// Place the data of the entries from `start` onwards one after the
+    /// other, following the last entry before them which has data
+    fn pack_entries(&mut self, start: usize) -> Result<(), PakError> {
+        let block_size = self.header.block_size;
+
+        let mut next_offset = match self.entries[..start].iter().rfind(|e| e.offset != 0) {
+            Some(e) => e
+                .offset
+                .checked_add(e.length.div_ceil(block_size))
+                .ok_or(PakError::DataTooLarge)?,
+            None => self.header.data_offset.div_ceil(block_size),
+        };
+
+        for entry in self.entries.iter_mut().skip(start) {
+            if entry.length == 0 {
+                entry.offset = 0;
+                continue;
+            }
+
+            entry.offset = next_offset;
+            next_offset = next_offset
+                .checked_add(entry.length.div_ceil(block_size))
+                .ok_or(PakError::DataTooLarge)?;
+        }
+
+        Ok(())
+    }
+
     /// Get the header information from the PAK
     pub fn header(&self) -> &Header {
         &self.header
@@ -395,7 +805,9 @@ impl Pak {
 
     /// Get an individual entry from the PAK by its ID
     pub fn get_entry_by_id(&mut self, id: u32) -> Option<&mut Entry> {
-        self.entries.get_mut((id - self.header.id_start) as usize)
+        let index = id.checked_sub(self.header.id_start)?;
+
+        self.entries.get_mut(index as usize)
     }
 
     pub fn get_entry_by_name(&mut self, name: &str) -> Option<&mut Entry> {
@@ -424,3 +836,30 @@ fn read_cstring<T: Seek + Read + BufRead>(input: &mut T) -> Result<String, io::E
 
     Ok(String::from_utf8_lossy(&string_buf).to_string())
 }
+
+/// Read `length` bytes, without allocating space for them all up front in
+/// case the input is shorter than it claims
+pub(crate) fn read_exact_len<T: Read + ?Sized>(
+    input: &mut T,
+    length: u64,
+) -> Result<Vec<u8>, io::Error> {
+    let mut data = Vec::new();
+    (&mut *input).take(length).read_to_end(&mut data)?;
+
+    if (data.len() as u64) < length {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    Ok(data)
+}
+
+/// Read the data of an entry from its position in the PAK
+fn read_data<T: Read + Seek>(
+    input: &mut T,
+    position: u64,
+    length: u32,
+) -> Result<Vec<u8>, io::Error> {
+    input.seek(SeekFrom::Start(position))?;
+
+    read_exact_len(input, length as u64)
+}
@@ -0,0 +1,78 @@
+use std::{
+    fmt::Debug,
+    io::{self, Read, Seek, SeekFrom, Write},
+    sync::{Arc, Mutex},
+};
+
+/// Anything the data of a lazily decoded PAK can be read from
+pub(crate) trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// The location of an entry's data in the file a PAK was lazily decoded
+/// from. The file is shared between all of the entries of the PAK.
+#[derive(Clone)]
+pub(crate) struct EntrySource {
+    reader: Arc<Mutex<dyn ReadSeek>>,
+
+    /// Position of the entry data in bytes from the start of the file
+    position: u64,
+}
+
+impl EntrySource {
+    /// Create a reader shared by all entries, which can be given to
+    /// [`EntrySource::new()`]
+    pub fn shared<T: Read + Seek + Send + 'static>(input: T) -> Arc<Mutex<dyn ReadSeek>> {
+        Arc::new(Mutex::new(input))
+    }
+
+    pub fn new(reader: &Arc<Mutex<dyn ReadSeek>>, position: u64) -> Self {
+        Self {
+            reader: Arc::clone(reader),
+            position,
+        }
+    }
+
+    /// Position of the entry data in bytes from the start of the file
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Read `length` bytes of entry data
+    pub fn read(&self, length: usize) -> Result<Vec<u8>, io::Error> {
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        reader.seek(SeekFrom::Start(self.position))?;
+
+        crate::read_exact_len(&mut *reader, length as u64)
+    }
+
+    /// Copy `length` bytes of entry data into an output, without reading it
+    /// all into memory
+    pub fn copy_to<T: Write + ?Sized>(&self, length: u64, output: &mut T) -> Result<(), io::Error> {
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        reader.seek(SeekFrom::Start(self.position))?;
+
+        let copied = io::copy(&mut (&mut *reader).take(length), output)?;
+        if copied != length {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        Ok(())
+    }
+}
+
+impl Debug for EntrySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntrySource")
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for EntrySource {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.reader, &other.reader) && self.position == other.position
+    }
+}
+
+impl Eq for EntrySource {}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{decode, encode};
+use luca_pak::{PakError, builder::PakBuilder, header::PakFlags};
+
+#[test]
+fn build_named_pak() {
+    let pak = PakBuilder::new(16)
+        .with_id_start(100)
+        .with_subdirectory("patch")
+        .with_entry("first.txt", b"hello world, this is long".to_vec())
+        .with_entry("empty.txt", Vec::new())
+        .with_entry("last.txt", b"bye".to_vec())
+        .build()
+        .unwrap();
+
+    let bytes = encode(&pak);
+    let decoded = decode(&bytes).unwrap();
+    let header = decoded.header();
+    assert_eq!(header.entry_count(), 3);
+    assert_eq!(header.id_start(), 100);
+    assert_eq!(header.data_offset() % 16, 0);
+
+    // Header, one word of unknown data, the entry locations and the names
+    let header_len = 36 + 4 + 3 * 8 + "patch first.txt empty.txt last.txt ".len() as u32;
+    assert_eq!(header.data_offset(), header_len.next_multiple_of(16));
+
+    let entries = decoded.entries();
+    assert_eq!(entries[0].name().as_deref(), Some("first.txt"));
+    assert_eq!(entries[0].as_bytes().unwrap(), b"hello world, this is long");
+    assert!(entries[1].is_empty());
+    assert_eq!(entries[2].as_bytes().unwrap(), b"bye");
+    assert_eq!(entries[2].id(), 102);
+
+    // The data of each entry starts on a block, with the gaps filled
+    let last_offset = header.data_offset() as usize + 32;
+    assert_eq!(&bytes[last_offset..], b"bye\0\0\0\0\0\0\0\0\0\0\0\0\0");
+
+    // Encoding again gives the same file
+    assert_eq!(encode(&decoded), bytes);
+}
+
+#[test]
+fn build_with_flags() {
+    // Unknown data for each entry and five words after the header, no names
+    let pak = PakBuilder::new(8)
+        .with_flags(PakFlags(0b00100000011))
+        .with_subdirectory("ignored")
+        .with_entry("a", vec![1; 9])
+        .with_entry("b", vec![2; 8])
+        .build()
+        .unwrap();
+
+    let bytes = encode(&pak);
+    let decoded = decode(&bytes).unwrap();
+    assert_eq!(decoded.header().data_offset(), 96);
+    assert_eq!(bytes.len(), 96 + 16 + 8);
+    assert!(decoded.entries().iter().all(|e| e.name().is_none()));
+    assert_eq!(decoded.entries()[1].as_bytes().unwrap(), [2; 8]);
+}
+
+#[test]
+fn build_rejects_bad_names() {
+    let duplicate = PakBuilder::new(16)
+        .with_entry("a.txt", Vec::new())
+        .with_entry("a.txt", Vec::new())
+        .build();
+    assert!(matches!(duplicate, Err(PakError::DuplicateName(n)) if n == "a.txt"));
+
+    let nul = PakBuilder::new(16).with_entry("a\0b", Vec::new()).build();
+    assert!(matches!(nul, Err(PakError::InvalidName(_))));
+
+    assert!(PakBuilder::new(0).build().is_err());
+}
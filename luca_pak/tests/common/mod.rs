@@ -0,0 +1,38 @@
+// Each test file only uses some of these
+#![allow(dead_code)]
+
+use std::{io::Cursor, path::PathBuf};
+
+use luca_pak::{Pak, PakError, PakLimits, builder::PakBuilder};
+
+/// Build a PAK archive with two named entries, with IDs starting at 5
+pub fn test_pak() -> Vec<u8> {
+    let pak = PakBuilder::new(16)
+        .with_id_start(5)
+        .with_entry("a.txt", b"hello".to_vec())
+        .with_entry("b.txt", b"world!".to_vec())
+        .build()
+        .unwrap();
+
+    encode(&pak)
+}
+
+pub fn encode(pak: &Pak) -> Vec<u8> {
+    let mut output = Vec::new();
+    pak.encode(&mut output).unwrap();
+
+    output
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Pak, PakError> {
+    Pak::decode(
+        &mut Cursor::new(bytes),
+        PathBuf::new(),
+        PakLimits::default(),
+    )
+}
+
+/// Encode a PAK and decode it again
+pub fn round_trip(pak: &Pak) -> Pak {
+    decode(&encode(pak)).unwrap()
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use std::{io::Cursor, path::PathBuf};
+
+use common::{round_trip, test_pak};
+use luca_pak::{Pak, PakError, PakLimits, builder::PakBuilder, header::PakFlags};
+
+fn names(pak: &Pak) -> Vec<&str> {
+    pak.entries()
+        .iter()
+        .map(|e| e.name().as_deref().unwrap())
+        .collect()
+}
+
+#[test]
+fn add_and_remove_named() {
+    let mut pak = PakBuilder::new(16)
+        .with_id_start(10)
+        .with_flags(PakFlags(0b01100000000))
+        .with_entry("a.txt", b"first".to_vec())
+        .with_entry("b.txt", b"second".to_vec())
+        .build()
+        .unwrap();
+    let data_offset = pak.header().data_offset();
+
+    pak.push_entry("c.txt", b"third").unwrap();
+    pak.insert_entry(0, "new.txt", b"inserted entry").unwrap();
+    assert!(matches!(
+        pak.push_entry("a.txt", b""),
+        Err(PakError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        pak.insert_entry(9, "d.txt", b""),
+        Err(PakError::IndexError)
+    ));
+
+    // The tables no longer fit before the data, so it has to move
+    assert!(pak.header().data_offset() > data_offset);
+
+    let decoded = round_trip(&pak);
+    assert_eq!(decoded.header().entry_count(), 4);
+    assert_eq!(names(&decoded), ["new.txt", "a.txt", "b.txt", "c.txt"]);
+    for (i, entry) in decoded.entries().iter().enumerate() {
+        assert_eq!((entry.index(), entry.id()), (i, 10 + i as u32));
+    }
+    assert_eq!(decoded.entries()[0].as_bytes().unwrap(), b"inserted entry");
+    assert_eq!(decoded.entries()[3].as_bytes().unwrap(), b"third");
+
+    let mut pak = decoded;
+    let removed = pak.remove_entry(1).unwrap();
+    assert_eq!(removed.as_bytes().unwrap(), b"first");
+
+    let decoded = round_trip(&pak);
+    assert_eq!(names(&decoded), ["new.txt", "b.txt", "c.txt"]);
+    assert_eq!(decoded.entries()[1].id(), 11);
+    assert_eq!(decoded.entries()[1].as_bytes().unwrap(), b"second");
+    assert_eq!(decoded.entries()[2].as_bytes().unwrap(), b"third");
+
+    // Removing a name frees up room in the header, the data stays in place
+    assert_eq!(decoded.header().data_offset(), pak.header().data_offset());
+}
+
+#[test]
+fn id_addressed_entries_stay() {
+    let mut pak = PakBuilder::new(16)
+        .with_flags(PakFlags(0b00000000001))
+        .with_entry("", b"first".to_vec())
+        .with_entry("", b"second".to_vec())
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        pak.insert_entry(0, "", b"data"),
+        Err(PakError::IdAddressed)
+    ));
+    assert!(matches!(pak.remove_entry(0), Err(PakError::IdAddressed)));
+
+    pak.push_entry("", b"third").unwrap();
+    pak.remove_entry(1).unwrap_err();
+    pak.remove_entry(2).unwrap();
+    pak.push_entry("", b"fourth").unwrap();
+
+    let decoded = round_trip(&pak);
+    let data: Vec<_> = decoded
+        .entries()
+        .iter()
+        .map(|e| e.as_bytes().unwrap())
+        .collect();
+    assert_eq!(data, [&b"first"[..], b"second", b"fourth"]);
+    assert_eq!(decoded.entries()[2].id(), 2);
+}
+
+#[test]
+fn rename_entries() {
+    let mut pak = PakBuilder::new(16)
+        .with_entry("a.txt", b"first".to_vec())
+        .with_entry("b.txt", b"second".to_vec())
+        .build()
+        .unwrap();
+    let data_offset = pak.header().data_offset();
+
+    pak.rename(0, "a much longer name for the first entry.txt")
+        .unwrap();
+    pak.rename(1, "b.txt").unwrap();
+    pak.set_subdirectory(Some("localized")).unwrap();
+    assert!(pak.header().data_offset() > data_offset);
+
+    assert!(matches!(
+        pak.rename(1, "a much longer name for the first entry.txt"),
+        Err(PakError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        pak.rename(0, "bad\0name"),
+        Err(PakError::InvalidName(_))
+    ));
+    assert!(matches!(pak.rename(2, "c.txt"), Err(PakError::IndexError)));
+
+    let mut decoded = round_trip(&pak);
+    assert_eq!(decoded.subdirectory().as_deref(), Some("localized"));
+    assert_eq!(
+        names(&decoded),
+        ["a much longer name for the first entry.txt", "b.txt"]
+    );
+    assert_eq!(decoded.entries()[0].as_bytes().unwrap(), b"first");
+    assert_eq!(decoded.entries()[1].as_bytes().unwrap(), b"second");
+
+    decoded.set_subdirectory(None).unwrap();
+    let decoded = round_trip(&decoded);
+    assert_eq!(decoded.subdirectory(), &None);
+    assert_eq!(decoded.entries()[1].as_bytes().unwrap(), b"second");
+
+    let mut unnamed = PakBuilder::new(16)
+        .with_flags(PakFlags(0))
+        .with_entry("", Vec::new())
+        .build()
+        .unwrap();
+    assert!(matches!(unnamed.rename(0, "a.txt"), Err(PakError::NoNames)));
+    assert!(matches!(
+        unnamed.set_subdirectory(Some("dir")),
+        Err(PakError::NoNames)
+    ));
+}
+
+#[test]
+fn failed_edits_leave_pak_unchanged() {
+    // Move the data of the second entry to the last block there can be, so
+    // the entries can't be moved any further along
+    let mut bytes = test_pak();
+    bytes[48..52].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut pak =
+        Pak::decode_lazy(Cursor::new(bytes), PathBuf::new(), PakLimits::default()).unwrap();
+
+    let header = pak.header().clone();
+    let entries = pak.entries().clone();
+
+    assert!(matches!(
+        pak.insert_entry(0, "new.txt", b"data"),
+        Err(PakError::DataTooLarge)
+    ));
+    assert!(matches!(
+        pak.push_entry("c.txt", b"data"),
+        Err(PakError::DataTooLarge)
+    ));
+
+    // Longer names no longer fit before the data either
+    assert!(matches!(
+        pak.rename(0, "a much longer name for the first entry.txt"),
+        Err(PakError::DataTooLarge)
+    ));
+    assert!(matches!(
+        pak.set_subdirectory(Some("a long subdirectory name")),
+        Err(PakError::DataTooLarge)
+    ));
+    assert_eq!(pak.subdirectory(), &None);
+
+    assert_eq!(pak.header().data_offset(), header.data_offset());
+    assert_eq!(pak.header().entry_count(), header.entry_count());
+    assert_eq!(pak.entries(), &entries);
+    assert_eq!(pak.entries()[0].as_bytes().unwrap(), b"hello");
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use std::{io::Cursor, path::PathBuf};
+
+use common::{decode, test_pak};
+use luca_pak::{Pak, PakError, PakLimits};
+
+#[test]
+fn truncated_files() {
+    let bytes = test_pak();
+
+    // Every truncated file must fail without panicking
+    for len in 0..bytes.len() - 10 {
+        assert!(decode(&bytes[..len]).is_err(), "decoded {} bytes", len);
+    }
+
+    // Cut off in the middle of the location of the second entry
+    let err = decode(&bytes[..36 + 4 + 8 + 2]).unwrap_err();
+    assert!(
+        matches!(err, PakError::EntryTableError(1, 48, _)),
+        "{:?}",
+        err
+    );
+
+    // Cut off in the middle of the data of the second entry
+    let err = decode(&bytes[..bytes.len() - 12]).unwrap_err();
+    assert!(
+        matches!(err, PakError::EntryDataError(1, 96, _)),
+        "{:?}",
+        err
+    );
+
+    // Lazily decoded entries report the same error when they are read
+    let lazy = Pak::decode_lazy(
+        Cursor::new(bytes[..bytes.len() - 12].to_vec()),
+        PathBuf::new(),
+        PakLimits::default(),
+    )
+    .unwrap();
+    assert_eq!(lazy.entries()[0].as_bytes().unwrap(), b"hello");
+    let err = lazy.entries()[1].file_type().unwrap_err();
+    assert!(
+        matches!(err, PakError::EntryDataError(1, 96, _)),
+        "{:?}",
+        err
+    );
+    let err = lazy.entries()[1].as_bytes().unwrap_err();
+    assert!(
+        matches!(err, PakError::EntryDataError(1, 96, _)),
+        "{:?}",
+        err
+    );
+    assert!(lazy.encode(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn invalid_headers() {
+    let mut bytes = test_pak();
+
+    // Entry data starts inside the entry tables
+    bytes[0] = 40;
+    assert!(matches!(decode(&bytes), Err(PakError::HeaderError)));
+
+    // A block size of 0
+    let mut bytes = test_pak();
+    bytes[12..16].fill(0);
+    assert!(matches!(decode(&bytes), Err(PakError::HeaderError)));
+
+    // An entry longer than the limit
+    let limits = PakLimits {
+        size_limit: 5,
+        ..Default::default()
+    };
+    let err = Pak::decode(&mut Cursor::new(test_pak()), PathBuf::new(), limits).unwrap_err();
+    assert!(
+        matches!(err, PakError::EntrySizeLimit(1, 6, 5)),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn entry_by_id() {
+    let mut pak = decode(&test_pak()).unwrap();
+
+    assert!(pak.get_entry_by_id(0).is_none());
+    assert!(pak.get_entry_by_id(7).is_none());
+    assert_eq!(
+        pak.get_entry_by_id(6).unwrap().name().as_deref(),
+        Some("b.txt")
+    );
+    assert!(matches!(
+        pak.replace_by_id(4, b"data"),
+        Err(PakError::IndexError)
+    ));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::{io::Cursor, path::PathBuf};
+
+use common::{decode, encode};
+use luca_pak::{Pak, PakLimits};
+
+/// Build a PAK archive with three named entries, the second of which is empty
+fn test_pak() -> Vec<u8> {
+    const BLOCK_SIZE: u32 = 4;
+    let names: &[u8] = b"a.txt\0b.txt\0c.txt\0";
+
+    // Header, one word of unknown data, the entry locations and the names
+    let data_offset = (36 + 4 + 3 * 8 + names.len() as u32).next_multiple_of(BLOCK_SIZE);
+    let first_block = data_offset / BLOCK_SIZE;
+
+    let mut pak = Vec::new();
+    for word in [data_offset, 3, 0, BLOCK_SIZE, 0, 0, 0, 0, 0b01000000000, 0] {
+        pak.extend_from_slice(&u32::to_le_bytes(word));
+    }
+    for (offset, length) in [(first_block, 5), (0, 0), (first_block + 2, 6)] {
+        pak.extend_from_slice(&u32::to_le_bytes(offset));
+        pak.extend_from_slice(&u32::to_le_bytes(length));
+    }
+    pak.extend_from_slice(names);
+    pak.resize(data_offset as usize, 0);
+    pak.extend_from_slice(b"hello\0\0\0world!\0\0");
+
+    pak
+}
+
+#[test]
+fn lazy_decode_matches() {
+    let bytes = test_pak();
+    let eager = decode(&bytes).unwrap();
+    let lazy = Pak::decode_lazy(
+        Cursor::new(bytes.clone()),
+        PathBuf::new(),
+        PakLimits::default(),
+    )
+    .unwrap();
+
+    assert!(
+        lazy.entries()
+            .iter()
+            .all(|e| e.is_empty() || !e.is_loaded())
+    );
+
+    // Untouched entries are copied from the source
+    assert_eq!(encode(&lazy), bytes);
+    assert!(
+        lazy.entries()
+            .iter()
+            .all(|e| e.is_empty() || !e.is_loaded())
+    );
+
+    for (a, b) in eager.entries().iter().zip(lazy.entries()) {
+        assert_eq!(a.file_type().unwrap(), b.file_type().unwrap());
+        assert_eq!(a.as_bytes().unwrap(), b.as_bytes().unwrap());
+        assert!(b.is_loaded());
+    }
+    assert_eq!(lazy.entries()[2].as_bytes().unwrap(), b"world!");
+    assert_eq!(encode(&lazy), bytes);
+    assert_eq!(eager.entries(), lazy.entries());
+}
+
+#[test]
+fn lazy_replace() {
+    let bytes = test_pak();
+    let mut eager = decode(&bytes).unwrap();
+    let mut lazy =
+        Pak::decode_lazy(Cursor::new(bytes), PathBuf::new(), PakLimits::default()).unwrap();
+
+    let replacement = b"a much longer replacement";
+    eager.replace(0, replacement).unwrap();
+    lazy.replace(0, replacement).unwrap();
+
+    // The last entry moves in the archive, but is still read from its old
+    // position in the source
+    assert!(!lazy.entries()[2].is_loaded());
+    assert_eq!(encode(&lazy), encode(&eager));
+
+    let decoded = decode(&encode(&lazy)).unwrap();
+    assert_eq!(decoded.entries()[0].as_bytes().unwrap(), replacement);
+    assert_eq!(decoded.entries()[2].as_bytes().unwrap(), b"world!");
+}
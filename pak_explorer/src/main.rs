@@ -1,12 +1,22 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use eframe::{egui::{
-    self, ColorImage, Image, ProgressBar, TextureFilter, TextureHandle, TextureOptions, ThemePreference
-}, icon_data};
-use kira::{sound::static_sound::{StaticSoundData, StaticSoundHandle}, AudioManager, AudioManagerSettings, DefaultBackend, Tween};
+use eframe::{
+    egui::{
+        self, ColorImage, Image, ProgressBar, TextureFilter, TextureHandle, TextureOptions,
+        ThemePreference,
+    },
+    icon_data,
+};
+use kira::{
+    AudioManager, AudioManagerSettings, DefaultBackend, Tween,
+    sound::static_sound::{StaticSoundData, StaticSoundHandle},
+};
 use log::error;
-use luca_pak::{entry::EntryType, Pak};
-use std::{fs, io::Cursor, time::Duration};
+use luca_pak::{
+    Pak,
+    entry::{Entry, EntryType},
+};
+use std::{error::Error, fs, io::Cursor, time::Duration};
 
 const APP_ID: &str = "dev.g2games.pak_explorer";
 
@@ -18,7 +28,9 @@ fn main() -> eframe::Result {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1024.0, 800.0])
-            .with_icon(icon_data::from_png_bytes(include_bytes!("../../assets/small_logo.png")).unwrap())
+            .with_icon(
+                icon_data::from_png_bytes(include_bytes!("../../assets/small_logo.png")).unwrap(),
+            )
             .with_app_id(APP_ID),
         persist_window: true,
         ..Default::default()
@@ -48,7 +60,8 @@ fn main() -> eframe::Result {
 struct PakExplorer {
     open_file: Option<Pak>,
     selected_entry: Option<luca_pak::entry::Entry>,
-    image_texture: Option<egui::TextureHandle>,
+    /// The decoded image of the selected entry, or why it couldn't be decoded
+    image_texture: Option<Result<egui::TextureHandle, String>>,
     hex_string: Option<Vec<String>>,
     audio_player: AudioManager,
     audio_handle: Option<StaticSoundHandle>,
@@ -63,34 +76,36 @@ impl eframe::App for PakExplorer {
 
             ui.horizontal(|ui| {
                 if ui.button("Open file").clicked()
-                    && let Some(path) = rfd::FileDialog::new().pick_file() {
-                        let pak = match Pak::open(&path) {
-                            Ok(pak) => Some(pak),
-                            Err(e) => {
-                                error!("Unable to read selected file as PAK: {}", e);
-                                None
-                            }
-                        };
-                        self.open_file = pak;
-                        self.selected_entry = None;
-                        self.image_texture = None;
-                        self.hex_string = None;
-
-                        if let Some(a) = self.audio_handle.as_mut() {
-                            a.stop(Tween::default());
+                    && let Some(path) = rfd::FileDialog::new().pick_file()
+                {
+                    let pak = match Pak::open(&path) {
+                        Ok(pak) => Some(pak),
+                        Err(e) => {
+                            error!("Unable to read selected file as PAK: {}", e);
+                            None
                         }
+                    };
+                    self.open_file = pak;
+                    self.selected_entry = None;
+                    self.image_texture = None;
+                    self.hex_string = None;
 
-                        self.audio_handle = None;
-                        self.audio_duration = None;
+                    if let Some(a) = self.audio_handle.as_mut() {
+                        a.stop(Tween::default());
                     }
+
+                    self.audio_handle = None;
+                    self.audio_duration = None;
+                }
                 if let Some(pak) = &self.open_file
                     && ui.button("Save PAK").clicked()
-                        && let Some(path) = rfd::FileDialog::new()
-                            .set_file_name(pak.path().file_name().unwrap().to_string_lossy())
-                            .save_file()
-                        {
-                            pak.save(&path).unwrap();
-                        }
+                    && let Some(path) = rfd::FileDialog::new()
+                        .set_file_name(pak.path().file_name().unwrap().to_string_lossy())
+                        .save_file()
+                    && let Err(e) = pak.save(&path)
+                {
+                    error!("Unable to save PAK: {}", e);
+                }
             });
 
             ui.separator();
@@ -149,24 +164,34 @@ impl eframe::App for PakExplorer {
                         && let Some(path) = rfd::FileDialog::new()
                             .set_file_name(entry.display_name())
                             .save_file()
-                        {
-                            entry.save(&path).unwrap();
-                        }
+                        && let Err(e) = entry.save(&path)
+                    {
+                        error!("Unable to save entry: {}", e);
+                    }
 
                     if let Some(pak) = &mut self.open_file.as_mut()
                         && ui.button("Replace entry").clicked()
-                            && let Some(path) = rfd::FileDialog::new().pick_file() {
-                                let file_bytes = fs::read(path).unwrap();
-                                pak.replace(entry.index(), &file_bytes).unwrap();
+                        && let Some(path) = rfd::FileDialog::new().pick_file()
+                    {
+                        match fs::read(path) {
+                            Ok(file_bytes) => {
+                                if let Err(e) = pak.replace(entry.index(), &file_bytes) {
+                                    error!("Unable to replace entry: {}", e);
+                                }
                             }
+                            Err(e) => error!("Unable to read replacement file: {}", e),
+                        }
+                    }
                 });
                 match entry.file_type() {
-                    EntryType::CZ0
-                    | EntryType::CZ1
-                    | EntryType::CZ2
-                    | EntryType::CZ3
-                    | EntryType::CZ4
-                    | EntryType::CZ5 => {
+                    Ok(
+                        EntryType::CZ0
+                        | EntryType::CZ1
+                        | EntryType::CZ2
+                        | EntryType::CZ3
+                        | EntryType::CZ4
+                        | EntryType::CZ5,
+                    ) => {
                         if ui.button("Save as PNG").clicked() {
                             let mut display_name = entry.display_name();
                             display_name.push_str(".png");
@@ -174,66 +199,84 @@ impl eframe::App for PakExplorer {
                                 .set_file_name(display_name)
                                 .save_file()
                             {
-                                let cz =
-                                    cz::CzFile::decode(&mut std::io::Cursor::new(entry.as_bytes()))
-                                        .unwrap();
-                                image::save_buffer_with_format(
-                                    path,
-                                    cz.as_raw(),
-                                    cz.header().width() as u32,
-                                    cz.header().height() as u32,
-                                    image::ColorType::Rgba8,
-                                    image::ImageFormat::Png,
-                                )
-                                .unwrap();
+                                let saved = decode_cz(entry).and_then(|cz| {
+                                    Ok(image::save_buffer_with_format(
+                                        path,
+                                        cz.as_raw(),
+                                        cz.header().width() as u32,
+                                        cz.header().height() as u32,
+                                        image::ColorType::Rgba8,
+                                        image::ImageFormat::Png,
+                                    )?)
+                                });
+                                if let Err(e) = saved {
+                                    error!("Unable to save entry as PNG: {}", e);
+                                }
                             }
                         }
 
                         ui.separator();
 
-                        let texture: &TextureHandle = self.image_texture.get_or_insert_with(|| {
-                            let cz =
-                                cz::CzFile::decode(&mut std::io::Cursor::new(entry.as_bytes()))
-                                    .unwrap();
-                            let image = ColorImage::from_rgba_unmultiplied(
-                                [cz.header().width() as usize, cz.header().height() as usize],
-                                cz.as_raw(),
-                            );
-                            ui.ctx().load_texture(
-                                "eventframe",
-                                image,
-                                TextureOptions {
-                                    magnification: TextureFilter::Nearest,
-                                    minification: TextureFilter::Linear,
-                                    ..Default::default()
-                                },
-                            )
-                        });
+                        let texture: &Result<TextureHandle, String> =
+                            self.image_texture.get_or_insert_with(|| {
+                                let cz = decode_cz(entry).map_err(|e| e.to_string())?;
+                                let image = ColorImage::from_rgba_unmultiplied(
+                                    [cz.header().width() as usize, cz.header().height() as usize],
+                                    cz.as_raw(),
+                                );
+                                Ok(ui.ctx().load_texture(
+                                    "eventframe",
+                                    image,
+                                    TextureOptions {
+                                        magnification: TextureFilter::Nearest,
+                                        minification: TextureFilter::Linear,
+                                        ..Default::default()
+                                    },
+                                ))
+                            });
 
-                        ui.centered_and_justified(|ui| {
-                            ui.add(
-                                Image::from_texture(texture)
-                                    .show_loading_spinner(true)
-                                    .shrink_to_fit()
-                                    .corner_radius(2.0),
-                            )
-                        });
+                        match texture {
+                            Ok(texture) => ui.centered_and_justified(|ui| {
+                                ui.add(
+                                    Image::from_texture(texture)
+                                        .show_loading_spinner(true)
+                                        .shrink_to_fit()
+                                        .corner_radius(2.0),
+                                )
+                            }),
+                            Err(e) => ui.centered_and_justified(|ui| {
+                                ui.label(format!("Unable to decode image: {}", e))
+                            }),
+                        };
                     }
-                    EntryType::OGG
-                    | EntryType::OGGPAK
-                    | EntryType::WAV => {
+                    Ok(EntryType::OGG | EntryType::OGGPAK | EntryType::WAV) => {
                         ui.separator();
 
                         ui.horizontal(|ui| {
                             if ui.button("▶").clicked() && self.audio_handle.is_none() {
-                                let sound_data = StaticSoundData::from_cursor(
-                                    Cursor::new(entry.cloned_bytes_fixed())
-                                )
-                                .unwrap()
-                                .volume(-8.0);
+                                let played = entry
+                                    .cloned_bytes_fixed()
+                                    .map_err(|e| e.to_string())
+                                    .and_then(|bytes| {
+                                        StaticSoundData::from_cursor(Cursor::new(bytes))
+                                            .map_err(|e| e.to_string())
+                                    })
+                                    .and_then(|sound_data| {
+                                        let sound_data = sound_data.volume(-8.0);
+                                        let duration = sound_data.duration();
+                                        self.audio_player
+                                            .play(sound_data)
+                                            .map(|handle| (handle, duration))
+                                            .map_err(|e| e.to_string())
+                                    });
 
-                                self.audio_duration = Some(sound_data.duration());
-                                self.audio_handle = Some(self.audio_player.play(sound_data.clone()).unwrap());
+                                match played {
+                                    Ok((handle, duration)) => {
+                                        self.audio_duration = Some(duration);
+                                        self.audio_handle = Some(handle);
+                                    }
+                                    Err(e) => error!("Unable to play entry: {}", e),
+                                }
                             }
 
                             if ui.button("⏹").clicked() && self.audio_handle.is_some() {
@@ -245,11 +288,20 @@ impl eframe::App for PakExplorer {
                             if let Some(a) = &self.audio_handle {
                                 let pos = a.position() as f32;
 
-                                ui.add(ProgressBar::new(
-                                    pos / self.audio_duration.as_ref().unwrap().as_secs_f32()
-                                ).corner_radius(1.0).text(format!("{:02.0}:{:02.0}", pos / 60.0, pos % 60.0)));
+                                ui.add(
+                                    ProgressBar::new(
+                                        pos / self.audio_duration.as_ref().unwrap().as_secs_f32(),
+                                    )
+                                    .corner_radius(1.0)
+                                    .text(format!(
+                                        "{:02.0}:{:02.0}",
+                                        pos / 60.0,
+                                        pos % 60.0
+                                    )),
+                                );
 
-                                if pos / self.audio_duration.as_ref().unwrap().as_secs_f32() > 0.99 {
+                                if pos / self.audio_duration.as_ref().unwrap().as_secs_f32() > 0.99
+                                {
                                     self.audio_handle.as_mut().unwrap().stop(Tween::default());
                                     self.audio_handle = None;
                                     self.audio_duration = None;
@@ -259,9 +311,14 @@ impl eframe::App for PakExplorer {
                             ui.request_repaint_after(Duration::from_millis(50));
                         });
                     }
-                    _ => {
+                    Ok(_) => {
                         ui.centered_and_justified(|ui| ui.label("No Preview Available"));
                     }
+                    Err(e) => {
+                        ui.centered_and_justified(|ui| {
+                            ui.label(format!("Unable to read entry: {}", e))
+                        });
+                    }
                 }
             } else if self.open_file.is_some() {
                 ui.centered_and_justified(|ui| ui.label("Select an Entry"));
@@ -269,3 +326,10 @@ impl eframe::App for PakExplorer {
         });
     }
 }
+
+/// Decode the data of an entry as a CZ image
+fn decode_cz(entry: &Entry) -> Result<cz::CzFile, Box<dyn Error>> {
+    let data = entry.as_bytes()?;
+
+    Ok(cz::CzFile::decode(&mut Cursor::new(data))?)
+}
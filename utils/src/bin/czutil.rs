@@ -1,10 +1,35 @@
-use clap::{error::ErrorKind, ArgAction, Error, Parser, Subcommand};
-use cz::{common::{CzVersion, ExtendedHeader}, CzFile};
-use image::ColorType;
-use lbee_utils::version;
+use clap::{ArgAction, Parser, Subcommand};
+use cz::{
+    CzFile, GrayscaleRamp, Palette, RGBA8, ResizeFilter,
+    common::{CommonHeader, CzError, CzVersion, ExtendedHeader},
+    diff::{BitmapDiff, header_differences},
+    dynamic::ConversionLoss,
+    info::{CzInfo, edit_header},
+    join_alpha, split_alpha,
+};
+use font8x8::{BASIC_FONTS, HIRAGANA_FONTS, LATIN_FONTS, UnicodeFonts};
+use image::{ColorType, ImageError, ImageFormat, Rgba, RgbaImage, imageops};
+use lbee_utils::{default_jobs, run_parallel, to_pretty_size, version, walk_files};
 use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    fs,
+    io::{BufReader, BufWriter, Cursor, Read, Write},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+#[cfg(target_os = "linux")]
 use std::{
-    fs, num::ParseIntError, path::{Path, PathBuf}, process::exit
+    collections::HashMap,
+    sync::mpsc,
+    time::{Duration, Instant},
 };
 
 /// Utility to maniuplate CZ image files from the LUCA System game engine by
@@ -24,22 +49,40 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Decode a CZ file to a PNG
+    /// Decode a CZ file to a PNG or another image format
     Decode {
         /// Decode a whole folder, and output to another folder
         #[arg(short, long)]
         batch: bool,
 
+        /// Write 8-bit images as paletted PNGs which keep the CZ palette
+        #[arg(long)]
+        indexed: bool,
+
+        /// Write a JSON file next to each image with the CZ parameters, which
+        /// encode reads to rebuild the file the same way
+        #[arg(short, long)]
+        metadata: bool,
+
+        /// Number of files to decode at once in batch mode
+        #[arg(short, long, value_name = "COUNT")]
+        jobs: Option<usize>,
+
+        /// Output image format (ex. png, webp, tga), chosen from the output
+        /// file extension if not given, and PNG otherwise
+        #[arg(short, long, value_name = "FORMAT", value_parser = parse_image_format)]
+        format: Option<ImageFormat>,
+
         /// Input CZ file of any type
         #[arg(value_name = "CZ FILE")]
         input: PathBuf,
 
-        /// Output PNG file location
+        /// Output image file location
         #[arg(value_name = "PATH")]
         output: Option<PathBuf>,
     },
 
-    /// Encode a PNG file to a CZ
+    /// Encode an image file to a CZ
     Encode {
         /// Input image to encode
         #[arg(value_name = "INPUT")]
@@ -49,14 +92,20 @@ enum Commands {
         #[arg(value_name = "OUTPUT")]
         output: PathBuf,
 
-        /// Output CZ file version
+        /// Output CZ file version, taken from the metadata or the file
+        /// extension if not given, and CZ3 otherwise
         #[arg(short, long, value_name = "CZ VERSION")]
         version: Option<u8>,
 
-        /// Output CZ file bit depth
+        /// Output CZ file bit depth, the smallest one which keeps all of the
+        /// image's colors is chosen if not given
         #[arg(short, long, value_name = "CZ BIT DEPTH")]
         depth: Option<u16>,
 
+        /// Try every CZ version and keep whichever gives the smallest file
+        #[arg(long, conflicts_with = "version")]
+        smallest: bool,
+
         /// Set the extended header crop (ex. 1280x720)
         #[arg(long, value_name = "CROP")]
         crop: Option<String>,
@@ -68,6 +117,15 @@ enum Commands {
         /// Set the extended header offset (ex. 82x73)
         #[arg(short, long, value_name = "OFFSET")]
         offset: Option<String>,
+
+        /// Store 8-bit grayscale input with an opaque gray palette instead of
+        /// the alpha mask palette
+        #[arg(long)]
+        luma: bool,
+
+        /// Ignore the JSON metadata file next to the input image, if any
+        #[arg(long)]
+        no_metadata: bool,
     },
 
     /// Replace an existing CZ file's image data
@@ -77,6 +135,10 @@ enum Commands {
         #[arg(short, long)]
         batch: bool,
 
+        /// Number of files to replace at once in batch mode
+        #[arg(short, long, value_name = "COUNT")]
+        jobs: Option<usize>,
+
         /// Original input CZ file of any type
         #[arg(value_name = "CZ FILE")]
         input: PathBuf,
@@ -97,7 +159,8 @@ enum Commands {
         #[arg(short, long, value_name = "BIT DEPTH")]
         depth: Option<u16>,
 
-        /// Do not clear and regenerate the palette, which causes issues with masks.
+        /// Do not clear and regenerate the palette. Grayscale masks replaced
+        /// with an 8-bit grayscale image always keep their palette.
         #[arg(long, action = ArgAction::SetFalse)]
         no_clear_palette: bool,
 
@@ -117,6 +180,288 @@ enum Commands {
         #[arg(short, long, value_name = "OFFSET")]
         offset: Option<String>,
     },
+
+    /// Watch a folder of images, and rebuild the matching CZ files whenever
+    /// one of the images is saved
+    #[cfg(target_os = "linux")]
+    Watch {
+        /// Folder of replacement images to watch
+        #[arg(value_name = "SOURCE")]
+        source: PathBuf,
+
+        /// Folder to write the rebuilt CZ files to, with the same layout as
+        /// the source folder
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+
+        /// Folder of the original CZ files. Every rebuild starts from these,
+        /// so it must not be the output folder.
+        #[arg(long, value_name = "FOLDER")]
+        originals: PathBuf,
+
+        /// Time to wait after the last change to an image before rebuilding
+        /// it, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 300)]
+        debounce: u64,
+    },
+
+    /// Export a CZ file as an RGB image and a separate grayscale alpha mask
+    Split {
+        /// Input CZ file of any type
+        #[arg(value_name = "CZ FILE")]
+        input: PathBuf,
+
+        /// Output RGB image location
+        #[arg(value_name = "RGB IMAGE")]
+        rgb: PathBuf,
+
+        /// Output alpha mask location, next to the RGB image with `_alpha`
+        /// added to the name if not given
+        #[arg(value_name = "ALPHA IMAGE")]
+        alpha: Option<PathBuf>,
+    },
+
+    /// Build a CZ file from an RGB image and a separate grayscale alpha mask
+    Merge {
+        /// Input RGB image
+        #[arg(value_name = "RGB IMAGE")]
+        rgb: PathBuf,
+
+        /// Input alpha mask, which must be the same size as the RGB image
+        #[arg(value_name = "ALPHA IMAGE")]
+        alpha: PathBuf,
+
+        /// Output CZ file location
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+
+        /// Output CZ file version
+        #[arg(short, long, value_name = "CZ VERSION", default_value_t = 4)]
+        version: u8,
+
+        /// Original CZ file to copy the extended header and color block from
+        #[arg(long, value_name = "CZ FILE")]
+        original: Option<PathBuf>,
+    },
+
+    /// Convert a CZ file to another CZ version and bit depth
+    Convert {
+        /// Input CZ file of any type
+        #[arg(value_name = "CZ FILE")]
+        input: PathBuf,
+
+        /// Output CZ file location
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+
+        /// Output CZ file version
+        #[arg(short, long, value_name = "CZ VERSION")]
+        to_version: u8,
+
+        /// Output CZ file bit depth, the input bit depth is kept if not given
+        #[arg(short, long, value_name = "CZ BIT DEPTH")]
+        depth: Option<u16>,
+    },
+
+    /// Resize CZ files, scaling their offset, crop and bounds to match
+    Resize {
+        /// Resize a whole folder, and output to another folder
+        #[arg(short, long)]
+        batch: bool,
+
+        /// Number of files to resize at once in batch mode
+        #[arg(short, long, value_name = "COUNT")]
+        jobs: Option<usize>,
+
+        /// Input CZ file of any type
+        #[arg(value_name = "CZ FILE")]
+        input: PathBuf,
+
+        /// Output CZ file location
+        #[arg(value_name = "PATH")]
+        output: PathBuf,
+
+        /// Scale the image by a factor (ex. 1.5 for 1280x720 to 1920x1080)
+        #[arg(short, long, value_name = "FACTOR", required_unless_present = "size")]
+        scale: Option<f64>,
+
+        /// Resize the image to an exact size (ex. 1920x1080)
+        #[arg(long, value_name = "SIZE", conflicts_with = "scale")]
+        size: Option<String>,
+
+        /// Resampling filter: nearest, bilinear or bicubic
+        #[arg(short, long, value_name = "FILTER", default_value = "bicubic")]
+        #[arg(value_parser = parse_resize_filter)]
+        filter: ResizeFilter,
+    },
+
+    /// Re-encode CZ files in whichever version and bit depth gives the
+    /// smallest file, without changing the image
+    Optimize {
+        /// Optimize a whole folder, and output to another folder
+        #[arg(short, long)]
+        batch: bool,
+
+        /// Number of files to optimize at once in batch mode
+        #[arg(short, long, value_name = "COUNT")]
+        jobs: Option<usize>,
+
+        /// Input CZ file of any type
+        #[arg(value_name = "CZ FILE")]
+        input: PathBuf,
+
+        /// Output CZ file location, the input is overwritten if not given
+        #[arg(value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Decode every CZ file in a folder into labeled thumbnails on contact
+    /// sheets
+    Gallery {
+        /// Folder of CZ files
+        #[arg(value_name = "FOLDER")]
+        input: PathBuf,
+
+        /// Contact sheet location, which is numbered if there are several
+        #[arg(short, long, value_name = "PATH", default_value = "sheet.png")]
+        output: PathBuf,
+
+        /// Largest width and height of a thumbnail
+        #[arg(short, long, value_name = "PIXELS", default_value_t = 192)]
+        size: u32,
+
+        /// Number of thumbnails in each row
+        #[arg(short, long, value_name = "COUNT", default_value_t = 8)]
+        columns: u32,
+
+        /// Number of thumbnails on each sheet, all of them go on one sheet if
+        /// not given
+        #[arg(short, long, value_name = "COUNT")]
+        per_page: Option<usize>,
+
+        /// Write an HTML index which links each thumbnail to a full size
+        /// export of the image
+        #[arg(long, value_name = "PATH")]
+        html: Option<PathBuf>,
+
+        /// Number of files to decode at once
+        #[arg(short, long, value_name = "COUNT")]
+        jobs: Option<usize>,
+    },
+
+    /// Check that every CZ file in a folder can be decoded and encoded again
+    /// without changing the image, and write a report of the failures
+    Verify {
+        /// Number of files to verify at once
+        #[arg(short, long, value_name = "COUNT")]
+        jobs: Option<usize>,
+
+        /// Report file location
+        #[arg(short, long, value_name = "PATH", default_value = "verify_report.txt")]
+        report: PathBuf,
+
+        /// Folder of CZ files, or a single CZ file
+        #[arg(value_name = "PATH")]
+        input: PathBuf,
+    },
+
+    /// Compare the pixels and headers of two images, each of which can be a
+    /// CZ file or another image
+    Diff {
+        /// First image to compare
+        #[arg(value_name = "IMAGE A")]
+        left: PathBuf,
+
+        /// Second image to compare
+        #[arg(value_name = "IMAGE B")]
+        right: PathBuf,
+
+        /// Write an image of the first one with the changed pixels highlighted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Show the header, palette and compression information of CZ files
+    Info {
+        /// Input CZ files of any type
+        #[arg(value_name = "CZ FILES", required = true)]
+        input: Vec<PathBuf>,
+
+        /// Print the information as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Change the header of a CZ file without re-encoding the image data
+    EditHeader {
+        /// Input CZ file of any type
+        #[arg(value_name = "CZ FILE")]
+        input: PathBuf,
+
+        /// Output CZ file location, the input is modified if not given
+        #[arg(value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Set the extended header crop (ex. 1280x720)
+        #[arg(long, value_name = "CROP")]
+        crop: Option<String>,
+
+        /// Set the extended header bounds (ex. 1280x720)
+        #[arg(long, value_name = "BOUNDS")]
+        bounds: Option<String>,
+
+        /// Set the extended header offset (ex. 82x73)
+        #[arg(short, long, value_name = "OFFSET")]
+        offset: Option<String>,
+
+        /// Set the unknown extended header width and height (ex. 1280x720)
+        #[arg(long, value_name = "SIZE")]
+        unknown_size: Option<String>,
+
+        /// Set the color block byte of the header
+        #[arg(long, value_name = "VALUE")]
+        color_block: Option<u8>,
+
+        /// Remove the extended header
+        #[arg(long, conflicts_with_all = ["crop", "bounds", "offset", "unknown_size"])]
+        remove_extended: bool,
+    },
+
+    /// Export or apply the palette of 8-bit CZ files
+    Palette {
+        #[command(subcommand)]
+        command: PaletteCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum PaletteCommands {
+    /// Export the palette of a CZ file as a GPL, ACT or PNG swatch file
+    Export {
+        /// Input 8-bit CZ file
+        #[arg(value_name = "CZ FILE")]
+        input: PathBuf,
+
+        /// Output palette file, the type is chosen by the extension
+        #[arg(value_name = "PALETTE")]
+        output: PathBuf,
+    },
+
+    /// Re-encode 8-bit CZ files with a palette from a GPL, ACT or PNG swatch
+    /// file, keeping their palette indices
+    Apply {
+        /// Palette file to apply
+        #[arg(value_name = "PALETTE")]
+        palette: PathBuf,
+
+        /// Input 8-bit CZ files
+        #[arg(value_name = "CZ FILES", required = true)]
+        input: Vec<PathBuf>,
+
+        /// Output folder, or output file if there is only one input
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+    },
 }
 
 fn main() {
@@ -140,6 +485,10 @@ fn main() {
             input,
             output,
             batch,
+            indexed,
+            metadata,
+            jobs,
+            format,
         } => {
             if !input.exists() {
                 pretty_error("The input file/folder provided does not exist");
@@ -157,68 +506,75 @@ fn main() {
                     exit(1);
                 }
 
-                for entry in fs::read_dir(input).unwrap() {
-                    let path = entry.unwrap().path();
-                    if !path.is_file() {
-                        continue;
+                let output = output.as_ref().unwrap();
+                let format = format.unwrap_or(ImageFormat::Png);
+                let files = match walk_files(input) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        pretty_error(&format!("Could not read input directory: {}", e));
+                        exit(1);
                     }
+                };
 
-                    let filename = PathBuf::from(path.file_name().unwrap());
-                    let filename = filename.with_extension("png");
+                // Decode all the files, mirroring the directory structure
+                let failures = run_parallel(&files, jobs.unwrap_or_else(default_jobs), |path| {
+                    let final_path = output
+                        .join(path.strip_prefix(input)?)
+                        .with_extension(format.extensions_str()[0]);
 
-                    let mut final_path = output.clone().unwrap();
-                    final_path.push(filename);
+                    if let Some(parent) = final_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
 
-                    let cz = match cz::open(&path) {
-                        Ok(cz) => cz,
-                        Err(_) => {
-                            pretty_error(&format!(
-                                "Could not open input as a CZ file: {}\n",
-                                path.into_os_string().to_str().unwrap()
-                            ));
-                            continue;
-                        }
-                    };
+                    let cz = cz::open(path)?;
+                    export_image(&cz, &final_path, format, *indexed)?;
 
-                    image::save_buffer_with_format(
-                        final_path,
-                        cz.as_raw(),
-                        cz.header().width() as u32,
-                        cz.header().height() as u32,
-                        ColorType::Rgba8,
-                        image::ImageFormat::Png,
-                    )
-                    .unwrap();
-                }
+                    if *metadata {
+                        Metadata::from_cz(&cz).save(&metadata_path(&final_path))?;
+                    }
+
+                    Ok(())
+                });
+
+                finish_batch("Decoded", &files, &failures, 0);
             } else {
-                let cz = cz::open(input).unwrap();
-
-                if let Some(output) = output {
-                    image::save_buffer_with_format(
-                        output,
-                        cz.as_raw(),
-                        cz.header().width() as u32,
-                        cz.header().height() as u32,
-                        ColorType::Rgba8,
-                        image::ImageFormat::Png,
-                    )
-                    .unwrap();
+                let cz = match cz::open(input) {
+                    Ok(cz) => cz,
+                    Err(e) => {
+                        pretty_error(&format!("Could not open input as a CZ file: {}", e));
+                        exit(1);
+                    }
+                };
+
+                let format = format.unwrap_or_else(|| {
+                    output
+                        .as_ref()
+                        .and_then(|o| ImageFormat::from_path(o).ok())
+                        .filter(|f| f.writing_enabled())
+                        .unwrap_or(ImageFormat::Png)
+                });
+
+                let output = if let Some(output) = output {
+                    output.clone()
                 } else {
-                    let file_stem = PathBuf::from(input.file_name().unwrap());
-                    image::save_buffer_with_format(
-                        file_stem.with_extension("png"),
-                        cz.as_raw(),
-                        cz.header().width() as u32,
-                        cz.header().height() as u32,
-                        ColorType::Rgba8,
-                        image::ImageFormat::Png,
-                    )
-                    .unwrap();
+                    PathBuf::from(input.file_name().unwrap())
+                        .with_extension(format.extensions_str()[0])
+                };
+
+                if let Err(e) = export_image(&cz, &output, format, *indexed) {
+                    pretty_error(&format!("Could not save decoded image: {}", e));
+                    exit(1);
+                }
+
+                if *metadata && let Err(e) = Metadata::from_cz(&cz).save(&metadata_path(&output)) {
+                    pretty_error(&format!("Could not save metadata: {}", e));
+                    exit(1);
                 }
             }
         }
         Commands::Replace {
             batch,
+            jobs,
             input,
             replacement,
             output,
@@ -267,49 +623,50 @@ fn main() {
                     exit(1);
                 }
 
-                if !output.is_dir() {
+                if output.is_file() {
                     pretty_error("Batch output location must be a directory");
                     exit(1);
                 }
 
-                // Replace all the files within the directory and print errors for them
-                for entry in fs::read_dir(input).unwrap() {
-                    let path = entry.unwrap().path();
-                    if !path.is_file() {
-                        continue;
+                let files = match walk_files(input) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        pretty_error(&format!("Could not read input directory: {}", e));
+                        exit(1);
                     }
-
-                    // Set the replacement image to the same name as the original file
-                    let mut final_replacement = replacement.to_path_buf();
-                    final_replacement
-                        .push(PathBuf::from(path.file_name().unwrap()).with_extension("png"));
-
-                    // Set the replacement image to the same name as the original file
-                    let mut final_output = output.to_path_buf();
-                    final_output.push(path.file_name().unwrap());
-
-                    if let Err(error) =
-                        replace_cz(
-                            &path,
-                            &final_output,
-                            &final_replacement,
-                            version,
-                            depth,
-                            *no_clear_palette,
-                            CropBoundReplacement {
-                                auto_replace: *no_auto_bounds,
-                                ..Default::default()
-                            }
-                        )
-                    {
-                        Error::raw(
-                            ErrorKind::ValueValidation,
-                            format!("{:?} - {}\n", path, error),
-                        )
-                        .print()
-                        .unwrap();
+                };
+
+                // Only replace the files which have a replacement image with
+                // the same name and relative path as the original file
+                let (files, skipped): (Vec<_>, Vec<_>) = files.into_iter().partition(|path| {
+                    path.strip_prefix(input)
+                        .is_ok_and(|r| find_image(&replacement.join(r)).is_some())
+                });
+
+                let failures = run_parallel(&files, jobs.unwrap_or_else(default_jobs), |path| {
+                    let relative = path.strip_prefix(input)?;
+                    let final_replacement = find_image(&replacement.join(relative)).unwrap();
+                    let final_output = output.join(relative);
+
+                    if let Some(parent) = final_output.parent() {
+                        fs::create_dir_all(parent)?;
                     }
-                }
+
+                    replace_cz(
+                        path,
+                        &final_output,
+                        &final_replacement,
+                        version,
+                        depth,
+                        *no_clear_palette,
+                        CropBoundReplacement {
+                            auto_replace: *no_auto_bounds,
+                            ..Default::default()
+                        },
+                    )
+                });
+
+                finish_batch("Replaced", &files, &failures, skipped.len());
             } else {
                 if !input.is_file() {
                     pretty_error("Input must be a file");
@@ -322,7 +679,7 @@ fn main() {
                 }
 
                 // Replace the input file with the new image
-                replace_cz(
+                if let Err(e) = replace_cz(
                     &input,
                     &output,
                     &replacement,
@@ -334,8 +691,11 @@ fn main() {
                         crop,
                         bounds,
                         offset,
-                    }
-                ).unwrap();
+                    },
+                ) {
+                    pretty_error(&format!("Could not replace file: {}", e));
+                    exit(1);
+                }
             }
         }
         Commands::Encode {
@@ -346,12 +706,29 @@ fn main() {
             crop,
             bounds,
             offset,
+            luma,
+            no_metadata,
+            smallest,
         } => {
             if !input.exists() {
                 pretty_error("The original file provided does not exist");
                 exit(1);
             }
 
+            // Parameters from decoding with metadata are used as defaults,
+            // which any options given here override
+            let metadata = if *no_metadata {
+                None
+            } else {
+                match Metadata::load(&metadata_path(input)) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        pretty_error(&format!("Could not read input metadata: {e}"));
+                        exit(1);
+                    }
+                }
+            };
+
             let Ok(crop) = parse_dimensions(crop) else {
                 pretty_error(&format!("\"{:?}\" is not a valid dimension", crop));
                 exit(1);
@@ -378,22 +755,32 @@ fn main() {
                         exit(1);
                     }
                 }
-            } else if output
-                .extension()
-                .is_some_and(|e| e.to_ascii_lowercase().to_string_lossy().starts_with("cz"))
+            } else if let Some(m) = &metadata {
+                match CzVersion::try_from(m.version) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        pretty_error(&format!("Invalid CZ version in metadata: {}", e));
+                        exit(1);
+                    }
+                }
+            } else if let Some(ext) = output.extension()
+                && ext.to_ascii_lowercase().to_string_lossy().starts_with("cz")
             {
-                let ext_string = output.extension().unwrap().to_string_lossy();
+                let ext_string = ext.to_string_lossy();
                 let last_char = ext_string.chars().last().unwrap();
                 match CzVersion::try_from(last_char) {
-                    Ok(v) => v,
+                    Ok(v) if ext_string.chars().count() == 3 => v,
+                    Ok(_) => {
+                        pretty_error(&format!("Invalid CZ type: {}", ext_string));
+                        exit(1);
+                    }
                     Err(e) => {
                         pretty_error(&format!("Invalid CZ type: {}", e));
                         exit(1);
                     }
                 }
             } else {
-                pretty_error("CZ version not specified or not parseable from file path");
-                exit(1);
+                CzVersion::CZ3
             };
 
             let image = match image::open(input) {
@@ -405,85 +792,1541 @@ fn main() {
             };
 
             let image_depth = image.color();
+            let depth = depth.or(metadata.as_ref().map(|m| m.depth));
 
-            let mut cz = CzFile::from_raw(
-                version,
-                image.width() as u16,
-                image.height() as u16,
-                image.to_rgba8().into_vec(),
-            );
-
-            // Set the bit-depth of the image
-            if let Some(d) = *depth {
-                if !(d == 8 || d == 24 || d == 32) {
-                    pretty_error(&format!(
-                        "The color depth provided is not valid. Choose from: {}",
-                        "8, 24, or 32".bright_magenta()
-                    ));
-                    exit(1);
-                }
-                cz.header_mut().set_depth(d);
-            } else {
-                cz.header_mut().set_depth(image_depth.bits_per_pixel());
+            if let Some(d) = depth
+                && !(d == 8 || d == 24 || d == 32)
+            {
+                pretty_error(&format!(
+                    "The color depth provided is not valid. Choose from: {}",
+                    "8, 24, or 32".bright_magenta()
+                ));
+                exit(1);
             }
 
-            let cz = if crop.is_some() || bounds.is_some() || offset.is_some() {
-                let mut ext_header = ExtendedHeader::new();
-
-                if let Some(c) = crop {
-                    ext_header.crop_width = c.0;
-                    ext_header.crop_height = c.1;
+            let indexed = match read_indexed_png(input) {
+                Ok(i) => i.filter(|_| depth.is_none_or(|d| d == 8)),
+                Err(e) => {
+                    pretty_error(&format!("Could not open input file: {e}"));
+                    exit(1);
                 }
+            };
 
-                if let Some(b) = bounds {
-                    ext_header.bounds_width = b.0;
-                    ext_header.bounds_height = b.1;
+            // Paletted input keeps its indices and palette, and grayscale
+            // input is stored through a grayscale palette as-is, like masks,
+            // instead of being quantized
+            let mut cz = if let Some((indices, palette)) = indexed {
+                match CzFile::from_indexed(
+                    version,
+                    image.width() as u16,
+                    image.height() as u16,
+                    indices,
+                    palette,
+                ) {
+                    Ok(cz) => cz,
+                    Err(e) => {
+                        pretty_error(&format!("Could not use the input palette: {e}"));
+                        exit(1);
+                    }
                 }
+            } else if image_depth == ColorType::L8 && depth.is_none_or(|d| d == 8) {
+                let metadata_ramp = metadata
+                    .as_ref()
+                    .and_then(|m| m.palette())
+                    .and_then(|p| p.grayscale_ramp());
+
+                let ramp = if *luma {
+                    GrayscaleRamp::Luma
+                } else {
+                    metadata_ramp.unwrap_or(GrayscaleRamp::Alpha)
+                };
 
-                if let Some(o) = offset {
-                    ext_header.offset_x = o.0;
-                    ext_header.offset_y = o.1;
+                match CzFile::from_luma8(
+                    version,
+                    image.width() as u16,
+                    image.height() as u16,
+                    image.as_luma8().unwrap(),
+                    ramp,
+                ) {
+                    Ok(cz) => cz,
+                    Err(e) => {
+                        pretty_error(&format!("Could not use the grayscale input: {e}"));
+                        exit(1);
+                    }
                 }
-
-                cz.with_extended_header(ext_header)
             } else {
-                cz
+                CzFile::from_raw(
+                    version,
+                    image.width() as u16,
+                    image.height() as u16,
+                    image.to_rgba8().into_vec(),
+                )
             };
 
-            cz.save_as_cz(output).expect("Saving CZ file failed");
-        }
-    }
-}
+            // Set the bit-depth of the image
+            if let Some(d) = depth {
+                cz.header_mut().set_depth(d);
+            } else if cz.indices().is_none() {
+                cz.set_auto_depth();
+            }
 
-#[derive(Default, Clone, Copy)]
-struct CropBoundReplacement {
-    pub auto_replace: bool,
-    pub crop: Option<(u16, u16)>,
-    pub bounds: Option<(u16, u16)>,
-    pub offset: Option<(u16, u16)>,
-}
+            if let Some(m) = &metadata {
+                cz.header_mut().set_color_block(m.color_block);
+
+                // Reuse the original palette as long as the image was not
+                // edited to use colors outside of it
+                if cz.header().depth() == 8
+                    && cz.palette().is_none()
+                    && let Some(palette) = m.palette()
+                {
+                    if palette.covers(cz.as_raw()) {
+                        *cz.palette_mut() = Some(palette);
+                    } else {
+                        pretty_warning(
+                            "Image has colors outside the original palette, generating a new one",
+                        );
+                    }
+                }
+            }
 
-/// Replace a CZ file with the bitmap of a PNG file
-fn replace_cz<P: ?Sized + AsRef<Path>>(
-    input_path: &P,
-    output_path: &P,
-    replacement_path: &P,
-    version: &Option<u8>,
-    depth: &Option<u16>,
-    palette_clear: bool,
-    cb_info: CropBoundReplacement,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let path = input_path.as_ref();
-    if !path.is_file() {
-        return Err("Input path is not a file".into());
-    }
+            let metadata_ext = metadata.as_ref().and_then(|m| m.extended_header());
+            let cz =
+                if metadata_ext.is_some() || crop.is_some() || bounds.is_some() || offset.is_some()
+                {
+                    let mut ext_header = metadata_ext.unwrap_or_default();
 
-    if !replacement_path.as_ref().exists() || !replacement_path.as_ref().is_file() {
-        return Err("Replacement path does not exist or is not a file".into());
+                    if let Some(c) = crop {
+                        ext_header.crop_width = c.0;
+                        ext_header.crop_height = c.1;
+                    }
+
+                    if let Some(b) = bounds {
+                        ext_header.bounds_width = b.0;
+                        ext_header.bounds_height = b.1;
+                    }
+
+                    if let Some(o) = offset {
+                        ext_header.offset_x = o.0;
+                        ext_header.offset_y = o.1;
+                    }
+
+                    cz.with_extended_header(ext_header)
+                } else {
+                    cz
+                };
+
+            if *smallest {
+                let cz_bytes = match encode_smallest(&cz, depth) {
+                    Ok(b) => b,
+                    Err(e) => {
+                        pretty_error(&format!("Could not encode CZ file: {e}"));
+                        exit(1);
+                    }
+                };
+
+                if let Err(e) = fs::write(output, cz_bytes) {
+                    pretty_error(&format!("Could not save output file: {e}"));
+                    exit(1);
+                }
+            } else if let Err(e) = cz.save_as_cz(output) {
+                pretty_error(&format!("Could not save output file: {e}"));
+                exit(1);
+            }
+        }
+        Commands::EditHeader {
+            input,
+            output,
+            crop,
+            bounds,
+            offset,
+            unknown_size,
+            color_block,
+            remove_extended,
+        } => {
+            if !input.is_file() {
+                pretty_error("The input file does not exist");
+                exit(1);
+            }
+
+            let Ok(crop) = parse_dimensions(crop) else {
+                pretty_error(&format!("\"{:?}\" is not a valid dimension", crop));
+                exit(1);
+            };
+
+            let Ok(bounds) = parse_dimensions(bounds) else {
+                pretty_error(&format!("\"{:?}\" is not a valid dimension", bounds));
+                exit(1);
+            };
+
+            let Ok(offset) = parse_dimensions(offset) else {
+                pretty_error(&format!("\"{:?}\" is not a valid offset", offset));
+                exit(1);
+            };
+
+            let Ok(unknown_size) = parse_dimensions(unknown_size) else {
+                pretty_error(&format!("\"{:?}\" is not a valid dimension", unknown_size));
+                exit(1);
+            };
+
+            let edit = |common: &mut CommonHeader, extended: &mut Option<ExtendedHeader>| {
+                if let Some(c) = color_block {
+                    common.set_color_block(*c);
+                }
+
+                if *remove_extended {
+                    *extended = None;
+                    return;
+                }
+
+                if crop.is_none() && bounds.is_none() && offset.is_none() && unknown_size.is_none()
+                {
+                    return;
+                }
+
+                let ext = extended.get_or_insert_with(ExtendedHeader::new);
+                if let Some(c) = crop {
+                    ext.crop_width = c.0;
+                    ext.crop_height = c.1;
+                }
+
+                if let Some(b) = bounds {
+                    ext.bounds_width = b.0;
+                    ext.bounds_height = b.1;
+                }
+
+                if let Some(o) = offset {
+                    ext.offset_x = o.0;
+                    ext.offset_y = o.1;
+                }
+
+                if let Some(u) = unknown_size {
+                    ext.unknown_width = Some(u.0);
+                    ext.unknown_height = Some(u.1);
+                }
+            };
+
+            // Read the whole file first so it can be modified in place
+            let original = match fs::read(input) {
+                Ok(o) => o,
+                Err(e) => {
+                    pretty_error(&format!("Could not open input file: {e}"));
+                    exit(1);
+                }
+            };
+
+            let mut edited = Vec::new();
+            if let Err(e) = edit_header(&mut Cursor::new(original), &mut edited, edit) {
+                pretty_error(&format!("Could not edit header: {}", e));
+                exit(1);
+            }
+
+            if let Err(e) = fs::write(output.as_ref().unwrap_or(input), edited) {
+                pretty_error(&format!("Could not save output file: {e}"));
+                exit(1);
+            }
+        }
+        #[cfg(target_os = "linux")]
+        Commands::Watch {
+            source,
+            output,
+            originals,
+            debounce,
+        } => {
+            if !source.is_dir() {
+                pretty_error("The source folder does not exist");
+                exit(1);
+            }
+
+            if !originals.is_dir() {
+                pretty_error("The originals folder does not exist");
+                exit(1);
+            }
+
+            // Rebuilding from the previous output would carry changes to the
+            // header and palette over from one edit to the next
+            if output
+                .canonicalize()
+                .is_ok_and(|o| originals.canonicalize().is_ok_and(|p| o == p))
+            {
+                pretty_error("The originals folder must not be the output folder");
+                exit(1);
+            }
+
+            let debounce = Duration::from_millis(*debounce);
+            if let Err(e) = watch(source, output, originals, debounce) {
+                pretty_error(&format!("Could not watch source folder: {}", e));
+                exit(1);
+            }
+        }
+        Commands::Split { input, rgb, alpha } => {
+            let alpha = alpha.clone().unwrap_or_else(|| {
+                let mut name = rgb.file_stem().unwrap_or_default().to_owned();
+                name.push("_alpha.");
+                name.push(rgb.extension().unwrap_or("png".as_ref()));
+
+                rgb.with_file_name(name)
+            });
+
+            if let Err(e) = split_cz(input, rgb, &alpha) {
+                pretty_error(&format!("Could not split file: {}", e));
+                exit(1);
+            }
+        }
+        Commands::Merge {
+            rgb,
+            alpha,
+            output,
+            version,
+            original,
+        } => {
+            let version = match CzVersion::try_from(*version) {
+                Ok(v) => v,
+                Err(_) => {
+                    pretty_error(&format!(
+                        "Invalid CZ version {}; must be 0, 1, 2, 3, or 4",
+                        version
+                    ));
+                    exit(1);
+                }
+            };
+
+            if let Err(e) = merge_cz(rgb, alpha, output, version, original.as_deref()) {
+                pretty_error(&format!("Could not merge images: {}", e));
+                exit(1);
+            }
+        }
+        Commands::Convert {
+            input,
+            output,
+            to_version,
+            depth,
+        } => {
+            let version = match CzVersion::try_from(*to_version) {
+                Ok(v) => v,
+                Err(_) => {
+                    pretty_error(&format!(
+                        "Invalid CZ version {}; must be 0, 1, 2, 3, or 4",
+                        to_version
+                    ));
+                    exit(1);
+                }
+            };
+
+            let mut cz = match cz::open(input) {
+                Ok(cz) => cz,
+                Err(e) => {
+                    pretty_error(&format!("Could not open input as a CZ file: {}", e));
+                    exit(1);
+                }
+            };
+
+            let depth = depth.unwrap_or(cz.header().depth());
+            match cz.convert(version, depth) {
+                Ok(losses) => {
+                    for loss in losses {
+                        pretty_warning(&loss.to_string());
+                    }
+                }
+                Err(e) => {
+                    pretty_error(&format!("Could not convert file: {}", e));
+                    exit(1);
+                }
+            }
+
+            if let Err(e) = cz.save_as_cz(output) {
+                pretty_error(&format!("Could not save output file: {e}"));
+                exit(1);
+            }
+        }
+        Commands::Resize {
+            batch,
+            jobs,
+            input,
+            output,
+            scale,
+            size,
+            filter,
+        } => {
+            let size = match parse_dimensions(size) {
+                Ok(s) => s,
+                Err(e) => {
+                    pretty_error(&format!("Invalid size: {}", e));
+                    exit(1);
+                }
+            };
+
+            let new_size = match (size, scale) {
+                (Some(size), _) => ResizeTo::Size(size),
+                (None, Some(scale)) if *scale > 0.0 => ResizeTo::Scale(*scale),
+                _ => {
+                    pretty_error("Scale must be greater than 0");
+                    exit(1);
+                }
+            };
+
+            if *batch {
+                if !input.is_dir() {
+                    pretty_error("Batch input must be a directory");
+                    exit(1);
+                }
+
+                if output.is_file() {
+                    pretty_error("Batch output location must be a directory");
+                    exit(1);
+                }
+
+                let files = match walk_files(input) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        pretty_error(&format!("Could not read input directory: {}", e));
+                        exit(1);
+                    }
+                };
+
+                let failures = run_parallel(&files, jobs.unwrap_or_else(default_jobs), |path| {
+                    let final_output = output.join(path.strip_prefix(input)?);
+                    if let Some(parent) = final_output.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+
+                    resize_cz(path, &final_output, new_size, *filter)
+                });
+
+                finish_batch("Resized", &files, &failures, 0);
+            } else {
+                if !input.is_file() {
+                    pretty_error("Input must be a file");
+                    exit(1);
+                }
+
+                if let Err(e) = resize_cz(input, output, new_size, *filter) {
+                    pretty_error(&format!("Could not resize file: {}", e));
+                    exit(1);
+                }
+            }
+        }
+        Commands::Optimize {
+            batch,
+            jobs,
+            input,
+            output,
+        } => {
+            if *batch {
+                if !input.is_dir() {
+                    pretty_error("Batch input must be a directory");
+                    exit(1);
+                }
+
+                if output.as_ref().is_some_and(|o| o.is_file()) {
+                    pretty_error("Batch output location must be a directory");
+                    exit(1);
+                }
+
+                let files = match walk_files(input) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        pretty_error(&format!("Could not read input directory: {}", e));
+                        exit(1);
+                    }
+                };
+
+                let total_before = AtomicU64::new(0);
+                let total_after = AtomicU64::new(0);
+                let failures = run_parallel(&files, jobs.unwrap_or_else(default_jobs), |path| {
+                    let final_output = match output {
+                        Some(o) => o.join(path.strip_prefix(input)?),
+                        None => path.clone(),
+                    };
+
+                    if let Some(parent) = final_output.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+
+                    let (before, after) = optimize_cz(path, &final_output)?;
+                    total_before.fetch_add(before, Ordering::Relaxed);
+                    total_after.fetch_add(after, Ordering::Relaxed);
+
+                    Ok(())
+                });
+
+                let (before, after) = (total_before.into_inner(), total_after.into_inner());
+                println!(
+                    "Total: {} -> {}, saved {}",
+                    to_pretty_size(before),
+                    to_pretty_size(after),
+                    to_pretty_size(before - after),
+                );
+
+                finish_batch("Optimized", &files, &failures, 0);
+            } else {
+                if !input.is_file() {
+                    pretty_error("Input must be a file");
+                    exit(1);
+                }
+
+                if let Err(e) = optimize_cz(input, output.as_ref().unwrap_or(input)) {
+                    pretty_error(&format!("Could not optimize file: {}", e));
+                    exit(1);
+                }
+            }
+        }
+        Commands::Gallery {
+            input,
+            output,
+            size,
+            columns,
+            per_page,
+            html,
+            jobs,
+        } => {
+            if !input.is_dir() {
+                pretty_error("The input folder does not exist");
+                exit(1);
+            }
+
+            if *size == 0 || *columns == 0 || *per_page == Some(0) {
+                pretty_error("The thumbnail size, columns and thumbnails per page must be above 0");
+                exit(1);
+            }
+
+            let files: Vec<PathBuf> = match walk_files(input) {
+                Ok(f) => f.into_iter().filter(|p| has_cz_magic(p)).collect(),
+                Err(e) => {
+                    pretty_error(&format!("Could not read input directory: {}", e));
+                    exit(1);
+                }
+            };
+
+            // Full size exports and thumbnails for the HTML index go in a
+            // folder next to it
+            let html_files = html.as_ref().map(|h| {
+                let mut name = h.file_stem().unwrap_or_default().to_owned();
+                name.push("_files");
+                h.with_file_name(name)
+            });
+
+            // Each thumbnail goes in the same place as its file in the list
+            let indices: Vec<usize> = (0..files.len()).collect();
+            let thumbnails = Mutex::new(vec![None; files.len()]);
+            let failures = run_parallel(&indices, jobs.unwrap_or_else(default_jobs), |&index| {
+                let path = &files[index];
+                let relative = path.strip_prefix(input)?;
+                let cz = cz::open(path)?;
+                let (width, height) = (cz.header().width() as u32, cz.header().height() as u32);
+
+                let Some(image) = RgbaImage::from_raw(width, height, cz.as_raw().clone()) else {
+                    return Err("Bitmap does not match the image size".into());
+                };
+                let thumbnail = make_thumbnail(&image, *size);
+
+                if let Some(html_files) = &html_files {
+                    let full_path = gallery_image_path(&html_files.join("full"), relative);
+                    let thumb_path = gallery_image_path(&html_files.join("thumbs"), relative);
+                    for parent in [full_path.parent(), thumb_path.parent()]
+                        .into_iter()
+                        .flatten()
+                    {
+                        fs::create_dir_all(parent)?;
+                    }
+
+                    export_image(&cz, &full_path, ImageFormat::Png, false)?;
+                    thumbnail.save_with_format(&thumb_path, ImageFormat::Png)?;
+                }
+
+                thumbnails.lock().unwrap()[index] = Some(thumbnail);
+
+                Ok(())
+            });
+
+            let entries: Vec<(String, RgbaImage)> = files
+                .iter()
+                .zip(thumbnails.into_inner().unwrap())
+                .filter_map(|(path, thumbnail)| {
+                    let relative = path.strip_prefix(input).unwrap_or(path);
+                    Some((relative.to_string_lossy().to_string(), thumbnail?))
+                })
+                .collect();
+
+            let pages: Vec<_> = entries
+                .chunks(per_page.unwrap_or(entries.len()).max(1))
+                .collect();
+            for (i, page) in pages.iter().enumerate() {
+                let path = if pages.len() > 1 {
+                    let mut name = output.file_stem().unwrap_or_default().to_owned();
+                    name.push(format!("_{}.", i + 1));
+                    name.push(output.extension().unwrap_or("png".as_ref()));
+                    output.with_file_name(name)
+                } else {
+                    output.clone()
+                };
+
+                if let Err(e) = make_contact_sheet(page, *size, *columns).save(&path) {
+                    pretty_error(&format!("Could not save contact sheet: {}", e));
+                    exit(1);
+                }
+            }
+
+            if let (Some(html), Some(html_files)) = (html, &html_files)
+                && let Err(e) = write_gallery_html(html, html_files, input, &entries, *size)
+            {
+                pretty_error(&format!("Could not write HTML index: {}", e));
+                exit(1);
+            }
+
+            finish_batch("Decoded", &files, &failures, 0);
+        }
+        Commands::Verify {
+            jobs,
+            report,
+            input,
+        } => {
+            let files = if input.is_dir() {
+                match walk_files(input) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        pretty_error(&format!("Could not read input directory: {}", e));
+                        exit(1);
+                    }
+                }
+            } else if input.is_file() {
+                vec![input.clone()]
+            } else {
+                pretty_error("The input file/folder provided does not exist");
+                exit(1);
+            };
+
+            // Only files which start like a CZ file are checked
+            let (files, skipped): (Vec<_>, Vec<_>) =
+                files.into_iter().partition(|path| has_cz_magic(path));
+
+            let stage_failures = [const { AtomicUsize::new(0) }; VerifyStage::ALL.len()];
+            let failures = run_parallel(&files, jobs.unwrap_or_else(default_jobs), |path| {
+                verify_cz(path).map_err(|(stage, message)| {
+                    stage_failures[stage as usize].fetch_add(1, Ordering::Relaxed);
+                    format!("{}: {}", stage, message).into()
+                })
+            });
+
+            if let Err(e) = write_verify_report(
+                report,
+                input,
+                &files,
+                &failures,
+                &stage_failures.map(|c| c.into_inner()),
+                skipped.len(),
+            ) {
+                pretty_error(&format!("Could not write report: {}", e));
+                exit(1);
+            }
+
+            finish_batch("Verified", &files, &failures, skipped.len());
+        }
+        Commands::Diff {
+            left,
+            right,
+            output,
+        } => {
+            let open = |path: &PathBuf| match open_any_image(path) {
+                Ok(i) => i,
+                Err(e) => {
+                    pretty_error(&format!("Could not open {}: {}", path.to_string_lossy(), e));
+                    exit(1);
+                }
+            };
+            let (left_cz, left_size, left_rgba) = open(left);
+            let (right_cz, right_size, right_rgba) = open(right);
+
+            let diff = match BitmapDiff::new(&left_rgba, left_size, &right_rgba, right_size) {
+                Ok(d) => d,
+                Err(e) => {
+                    pretty_error(&format!("Could not compare images: {}", e));
+                    exit(1);
+                }
+            };
+
+            if left_size != right_size {
+                println!(
+                    "Dimensions: {}x{} -> {}x{}",
+                    left_size.0, left_size.1, right_size.0, right_size.1
+                );
+            }
+
+            if let (Some(l), Some(r)) = (&left_cz, &right_cz) {
+                let none = || String::from("none");
+                let differences = header_differences(l, r);
+
+                // The size is already shown for any kind of image
+                for difference in differences.into_iter().filter(|d| d.field != "size") {
+                    println!(
+                        "Header {}: {} -> {}",
+                        difference.field,
+                        difference.left.unwrap_or_else(none),
+                        difference.right.unwrap_or_else(none),
+                    );
+                }
+            }
+
+            let total = diff.size().0 as u64 * diff.size().1 as u64;
+            println!(
+                "Changed pixels: {} of {} ({:.2}%)",
+                diff.changed_pixels(),
+                total,
+                diff.changed_pixels() as f64 / total.max(1) as f64 * 100.0,
+            );
+
+            if let Some((x, y, width, height)) = diff.bounding_box() {
+                println!("Changed area: {}x{} at {}x{}", width, height, x, y);
+            }
+
+            if let Some(output) = output {
+                let highlight = diff.highlight(&left_rgba, left_size);
+                if let Err(e) = image::save_buffer(
+                    output,
+                    &highlight,
+                    diff.size().0,
+                    diff.size().1,
+                    ColorType::Rgba8,
+                ) {
+                    pretty_error(&format!("Could not save diff image: {}", e));
+                    exit(1);
+                }
+            }
+        }
+        Commands::Info { input, json } => {
+            let mut failed = false;
+            let mut json_infos = Vec::new();
+            for path in input {
+                let info = match CzInfo::open(path) {
+                    Ok(i) => i,
+                    Err(e) => {
+                        pretty_error(&format!("{}: {}", path.to_string_lossy(), e));
+                        failed = true;
+                        continue;
+                    }
+                };
+
+                if *json {
+                    json_infos.push(info_json(path, &info));
+                } else {
+                    print_info(path, &info);
+                }
+            }
+
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&json_infos).unwrap());
+            }
+
+            if failed {
+                exit(1);
+            }
+        }
+        Commands::Palette { command } => match command {
+            PaletteCommands::Export { input, output } => {
+                if let Err(e) = export_palette(input, output) {
+                    pretty_error(&format!("Could not export palette: {}", e));
+                    exit(1);
+                }
+            }
+            PaletteCommands::Apply {
+                palette,
+                input,
+                output,
+            } => {
+                let palette = match Palette::load(palette) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        pretty_error(&format!("Could not load palette: {}", e));
+                        exit(1);
+                    }
+                };
+
+                if input.len() > 1 && !output.is_dir() {
+                    pretty_error("Output must be a directory when applying to multiple files");
+                    exit(1);
+                }
+
+                let mut failed = false;
+                for path in input {
+                    let final_output = if output.is_dir() {
+                        output.join(path.file_name().unwrap())
+                    } else {
+                        output.clone()
+                    };
+
+                    if let Err(e) = apply_palette(path, &final_output, &palette) {
+                        pretty_error(&format!("{}: {}", path.to_string_lossy(), e));
+                        failed = true;
+                    }
+                }
+
+                if failed {
+                    exit(1);
+                }
+            }
+        },
+    }
+}
+
+/// Print the information of a CZ file in a human readable format
+fn print_info(path: &Path, info: &CzInfo) {
+    let header = info.header();
+    print!("{}:", path.to_string_lossy());
+    print!(" {:?}", header.version());
+    print!(", {}x{}", header.width(), header.height());
+    print!(", {} bpp", header.depth());
+    print!(", color block {}", header.color_block());
+    print!(", header length {}", header.length());
+    println!();
+
+    if let Some(ext) = info.extended_header() {
+        print!("  Extended header:");
+        print!(" offset {}x{}", ext.offset_x, ext.offset_y);
+        print!(", crop {}x{}", ext.crop_width, ext.crop_height);
+        print!(", bounds {}x{}", ext.bounds_width, ext.bounds_height);
+        if let (Some(w), Some(h)) = (ext.unknown_width, ext.unknown_height) {
+            print!(", unknown size {}x{}", w, h);
+        }
+        print!(", unknown 1: {}", ext.unknown_1());
+        if let Some(u) = ext.unknown_2() {
+            print!(", unknown 2: {}", u);
+        }
+        println!();
+    }
+
+    if let Some(palette) = info.palette() {
+        println!("  Palette: {} colors", palette.len());
+    }
+
+    if let Some(compression) = info.compression() {
+        let total_raw: usize = compression.chunks.iter().map(|c| c.size_raw).sum();
+        print!("  Compression: {} chunks", compression.chunk_count);
+        print!(", {} compressed", compression.total_size_compressed);
+        print!(", {} raw", total_raw);
+        println!();
+
+        for (i, chunk) in compression.chunks.iter().enumerate() {
+            println!(
+                "    {:<3} {:>8} -> {:>8}",
+                i, chunk.size_compressed, chunk.size_raw
+            );
+        }
+    }
+}
+
+/// Get the information of a CZ file as JSON
+fn info_json(path: &Path, info: &CzInfo) -> serde_json::Value {
+    let header = info.header();
+
+    let extended = info.extended_header().map(|ext| {
+        json!({
+            "offset_x": ext.offset_x,
+            "offset_y": ext.offset_y,
+            "crop_width": ext.crop_width,
+            "crop_height": ext.crop_height,
+            "bounds_width": ext.bounds_width,
+            "bounds_height": ext.bounds_height,
+            "unknown_width": ext.unknown_width,
+            "unknown_height": ext.unknown_height,
+            "unknown_1": ext.unknown_1(),
+            "unknown_2": ext.unknown_2(),
+        })
+    });
+
+    let compression = info.compression().as_ref().map(|c| {
+        json!({
+            "chunk_count": c.chunk_count,
+            "total_size_compressed": c.total_size_compressed,
+            "total_size_raw": c.chunks.iter().map(|c| c.size_raw).sum::<usize>(),
+            "chunks": c.chunks.iter().map(|c| json!({
+                "size_compressed": c.size_compressed,
+                "size_raw": c.size_raw,
+            })).collect::<Vec<_>>(),
+        })
+    });
+
+    json!({
+        "path": path.to_string_lossy(),
+        "version": header.version() as u8,
+        "width": header.width(),
+        "height": header.height(),
+        "depth": header.depth(),
+        "color_block": header.color_block(),
+        "header_length": header.length(),
+        "extended_header": extended,
+        "palette_size": info.palette().as_ref().map(|p| p.len()),
+        "compression": compression,
+    })
+}
+
+/// The size to resize an image to
+#[derive(Clone, Copy)]
+enum ResizeTo {
+    Size((u16, u16)),
+    Scale(f64),
+}
+
+/// Resize a CZ file, keeping its version and bit depth
+fn resize_cz(
+    input: &Path,
+    output: &Path,
+    new_size: ResizeTo,
+    filter: ResizeFilter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cz = cz::open(input)?;
+
+    let (width, height) = match new_size {
+        ResizeTo::Size(size) => size,
+        ResizeTo::Scale(scale) => {
+            let scaled = |v: u16| (v as f64 * scale).round().clamp(1.0, u16::MAX as f64) as u16;
+            (scaled(cz.header().width()), scaled(cz.header().height()))
+        }
+    };
+
+    cz.resize(width, height, filter)?;
+    cz.save_as_cz(output)?;
+
+    Ok(())
+}
+
+/// Re-encode a CZ file as small as possible, keeping the original data if it
+/// is already the smallest, and return the file size before and after
+fn optimize_cz(input: &Path, output: &Path) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+    let original = fs::read(input)?;
+    let cz = CzFile::decode(&mut Cursor::new(&original))?;
+
+    let Some(optimized) = cz.optimize() else {
+        return Err("The image could not be encoded again without changing it".into());
+    };
+
+    let header = cz.header();
+    let new_header = optimized.cz.header();
+    let (before, after) = (original.len() as u64, optimized.bytes.len() as u64);
+
+    if after < before {
+        fs::write(output, &optimized.bytes)?;
+        println!(
+            "{}: {:?} {} bpp -> {:?} {} bpp, {} -> {} bytes ({:.1}% smaller)",
+            input.to_string_lossy(),
+            header.version(),
+            header.depth(),
+            new_header.version(),
+            new_header.depth(),
+            before,
+            after,
+            (before - after) as f64 / before as f64 * 100.0,
+        );
+
+        Ok((before, after))
+    } else {
+        if input != output {
+            fs::write(output, &original)?;
+        }
+        println!(
+            "{}: already optimal, {} bytes",
+            input.to_string_lossy(),
+            before
+        );
+
+        Ok((before, before))
+    }
+}
+
+/// Encode a CZ file in each CZ version, and return the smallest result
+fn encode_smallest(cz: &CzFile, depth: Option<u16>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut smallest: Option<Vec<u8>> = None;
+
+    for version in [
+        CzVersion::CZ0,
+        CzVersion::CZ1,
+        CzVersion::CZ2,
+        CzVersion::CZ3,
+        CzVersion::CZ4,
+    ] {
+        let mut candidate = cz.clone();
+
+        // CZ4 has to be 32-bit, which is fine unless a depth was asked for,
+        // but versions which drop the extended header are skipped
+        let Ok(losses) = candidate
+            .convert(version, cz.header().depth())
+            .or_else(|e| {
+                if version == CzVersion::CZ4 && depth.is_none() {
+                    candidate.convert(version, 32)
+                } else {
+                    Err(e)
+                }
+            })
+        else {
+            continue;
+        };
+
+        if losses.contains(&ConversionLoss::ExtendedHeader) {
+            continue;
+        }
+
+        let mut cz_bytes = Cursor::new(Vec::new());
+        candidate.encode(&mut cz_bytes)?;
+        let cz_bytes = cz_bytes.into_inner();
+
+        if smallest.as_ref().is_none_or(|s| cz_bytes.len() < s.len()) {
+            smallest = Some(cz_bytes);
+        }
+    }
+
+    smallest.ok_or_else(|| "The image cannot be stored in any CZ version".into())
+}
+
+/// Background color of contact sheets
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([0x20, 0x20, 0x20, 0xFF]);
+
+/// Space around each thumbnail on a contact sheet, in pixels
+const SHEET_PADDING: u32 = 8;
+
+/// Height of a thumbnail label, which is a single line of 8x8 characters
+const LABEL_HEIGHT: u32 = 8;
+
+/// Scale an image down so its width and height are at most the given size
+fn make_thumbnail(image: &RgbaImage, size: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width <= size && height <= size {
+        return image.clone();
+    }
+
+    let scale = size as f64 / width.max(height) as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+
+    imageops::thumbnail(image, new_width, new_height)
+}
+
+/// Tile labeled thumbnails on a sheet, with transparency shown as a checkerboard
+fn make_contact_sheet(entries: &[(String, RgbaImage)], size: u32, columns: u32) -> RgbaImage {
+    let columns = columns.min(entries.len() as u32).max(1);
+    let rows = (entries.len() as u32).div_ceil(columns).max(1);
+
+    let cell_width = size + SHEET_PADDING;
+    let cell_height = size + LABEL_HEIGHT + SHEET_PADDING * 2;
+    let mut sheet = RgbaImage::from_pixel(
+        columns * cell_width + SHEET_PADDING,
+        rows * cell_height + SHEET_PADDING,
+        SHEET_BACKGROUND,
+    );
+
+    for (i, (label, thumbnail)) in entries.iter().enumerate() {
+        let cell_x = (i as u32 % columns) * cell_width + SHEET_PADDING;
+        let cell_y = (i as u32 / columns) * cell_height + SHEET_PADDING;
+
+        // Center the thumbnail in the area above the label
+        let x = cell_x + (size - thumbnail.width()) / 2;
+        let y = cell_y + (size - thumbnail.height()) / 2;
+
+        let mut tile = RgbaImage::from_fn(thumbnail.width(), thumbnail.height(), |x, y| {
+            if (x / 8 + y / 8) % 2 == 0 {
+                Rgba([0x66, 0x66, 0x66, 0xFF])
+            } else {
+                Rgba([0x99, 0x99, 0x99, 0xFF])
+            }
+        });
+        imageops::overlay(&mut tile, thumbnail, 0, 0);
+        imageops::replace(&mut sheet, &tile, x as i64, y as i64);
+
+        draw_label(
+            &mut sheet,
+            label,
+            cell_x,
+            cell_y + size + SHEET_PADDING / 2,
+            size,
+        );
+    }
+
+    sheet
+}
+
+/// Draw a line of text with an 8x8 pixel font, keeping the end of the text if
+/// it is wider than the given width
+fn draw_label(image: &mut RgbaImage, text: &str, x: u32, y: u32, width: u32) {
+    // Thumbnails too narrow to fit the marker and a character go unlabeled
+    let max_chars = (width / 8) as usize;
+    if max_chars < 2 {
+        return;
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let visible = if chars.len() > max_chars {
+        let mut visible = vec!['~'];
+        visible.extend_from_slice(&chars[chars.len() + 1 - max_chars..]);
+        visible
+    } else {
+        chars
+    };
+
+    for (i, c) in visible.into_iter().enumerate() {
+        let glyph = BASIC_FONTS
+            .get(c)
+            .or_else(|| LATIN_FONTS.get(c))
+            .or_else(|| HIRAGANA_FONTS.get(c))
+            .or_else(|| BASIC_FONTS.get('?'))
+            .unwrap_or_default();
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..8 {
+                if bits & (1 << column) != 0 {
+                    let px = x + i as u32 * 8 + column;
+                    let py = y + row as u32;
+                    image.put_pixel(px, py, Rgba([0xE0, 0xE0, 0xE0, 0xFF]));
+                }
+            }
+        }
+    }
+}
+
+/// Write an HTML index of thumbnails which link to the full size images
+fn write_gallery_html(
+    path: &Path,
+    files_dir: &Path,
+    input: &Path,
+    entries: &[(String, RgbaImage)],
+    size: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut html = BufWriter::new(fs::File::create(path)?);
+    let files_name = files_dir.file_name().unwrap_or_default().to_string_lossy();
+    let title = escape_html(&input.to_string_lossy());
+
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, "<html>")?;
+    writeln!(html, "<head>")?;
+    writeln!(html, "<meta charset=\"utf-8\">")?;
+    writeln!(html, "<title>{}</title>", title)?;
+    writeln!(html, "<style>")?;
+    writeln!(
+        html,
+        "body {{ background: #202020; color: #e0e0e0; font-family: sans-serif; }}"
+    )?;
+    writeln!(
+        html,
+        ".gallery {{ display: flex; flex-wrap: wrap; gap: 8px; }}"
+    )?;
+    writeln!(html, "figure {{ margin: 0; width: {}px; }}", size)?;
+    writeln!(
+        html,
+        "figcaption {{ font-size: 12px; overflow-wrap: anywhere; }}"
+    )?;
+    writeln!(html, "</style>")?;
+    writeln!(html, "</head>")?;
+    writeln!(html, "<body>")?;
+    writeln!(html, "<h1>{}</h1>", title)?;
+    writeln!(html, "<div class=\"gallery\">")?;
+
+    for (name, _) in entries {
+        let image = gallery_image_path(Path::new(""), Path::new(name));
+        let image = escape_url(&image.to_string_lossy().replace('\\', "/"));
+        let files_name = escape_url(&files_name);
+
+        writeln!(
+            html,
+            "<figure><a href=\"{0}/full/{1}\"><img src=\"{0}/thumbs/{1}\" loading=\"lazy\"></a>\
+            <figcaption>{2}</figcaption></figure>",
+            files_name,
+            image,
+            escape_html(name),
+        )?;
+    }
+
+    writeln!(html, "</div>")?;
+    writeln!(html, "</body>")?;
+    writeln!(html, "</html>")?;
+
+    Ok(())
+}
+
+/// Location of an exported gallery image, which keeps the CZ file's extension
+/// so files which only differ by it do not overwrite each other
+fn gallery_image_path(dir: &Path, relative: &Path) -> PathBuf {
+    let mut path = dir.join(relative).into_os_string();
+    path.push(".png");
+
+    PathBuf::from(path)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encode everything in a relative URL apart from unreserved
+/// characters and slashes
+fn escape_url(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// The stages of verifying a CZ file, in order
+#[derive(Clone, Copy)]
+enum VerifyStage {
+    Decode,
+    Encode,
+    DecodeAgain,
+    Compare,
+}
+
+impl VerifyStage {
+    const ALL: [Self; 4] = [Self::Decode, Self::Encode, Self::DecodeAgain, Self::Compare];
+}
+
+impl std::fmt::Display for VerifyStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decode => write!(f, "decode"),
+            Self::Encode => write!(f, "encode"),
+            Self::DecodeAgain => write!(f, "decode again"),
+            Self::Compare => write!(f, "compare"),
+        }
+    }
+}
+
+/// Decode a CZ file, encode it again in the same version and bit depth, and
+/// check that decoding the new file gives the same pixels
+fn verify_cz(path: &Path) -> Result<(), (VerifyStage, String)> {
+    let original = cz::open(path).map_err(|e| (VerifyStage::Decode, e.to_string()))?;
+
+    let mut cz_bytes = Cursor::new(Vec::new());
+    original
+        .encode(&mut cz_bytes)
+        .map_err(|e| (VerifyStage::Encode, e.to_string()))?;
+    cz_bytes.set_position(0);
+
+    let decoded =
+        CzFile::decode(&mut cz_bytes).map_err(|e| (VerifyStage::DecodeAgain, e.to_string()))?;
+
+    let diff = BitmapDiff::from_cz(&original, &decoded)
+        .map_err(|e| (VerifyStage::Compare, e.to_string()))?;
+    if !diff.is_identical() {
+        let message = format!("{} pixels changed", diff.changed_pixels());
+        return Err((VerifyStage::Compare, message));
+    }
+
+    Ok(())
+}
+
+/// Check whether a file starts with the magic bytes of a CZ file
+fn has_cz_magic(path: &Path) -> bool {
+    let mut magic = [0u8; 3];
+    let read = fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic));
+
+    read.is_ok() && magic[0..2] == *b"CZ" && magic[2].is_ascii_digit()
+}
+
+/// Write the results of verifying files to a report
+fn write_verify_report(
+    path: &Path,
+    input: &Path,
+    files: &[PathBuf],
+    failures: &[(usize, String)],
+    stage_failures: &[usize],
+    skipped: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut report = BufWriter::new(fs::File::create(path)?);
+
+    writeln!(
+        report,
+        "CZ verification report for {}",
+        input.to_string_lossy()
+    )?;
+    writeln!(report, "czutil {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(report)?;
+    writeln!(report, "Files checked: {}", files.len())?;
+    writeln!(report, "Passed: {}", files.len() - failures.len())?;
+    writeln!(report, "Failed: {}", failures.len())?;
+    for (stage, count) in VerifyStage::ALL.iter().zip(stage_failures) {
+        writeln!(report, "  at {}: {}", stage, count)?;
+    }
+    writeln!(report, "Skipped, not CZ files: {}", skipped)?;
+
+    if !failures.is_empty() {
+        writeln!(report)?;
+        writeln!(report, "Failures:")?;
+        for (i, error) in failures {
+            writeln!(report, "{}: {}", files[*i].to_string_lossy(), error)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A CZ file if the image is one, along with the size and RGBA bitmap
+type AnyImage = (Option<CzFile>, (u32, u32), Vec<u8>);
+
+/// Open an image which can be either a CZ file or another image
+fn open_any_image(path: &Path) -> Result<AnyImage, Box<dyn std::error::Error>> {
+    match cz::open(path) {
+        Ok(cz) => {
+            let size = (cz.header().width() as u32, cz.header().height() as u32);
+            let rgba = cz.as_raw().clone();
+
+            Ok((Some(cz), size, rgba))
+        }
+        Err(CzError::NotCzFile) => {
+            let image = image::open(path)?.into_rgba8();
+
+            Ok((None, image.dimensions(), image.into_raw()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Watch a folder for saved images, and replace the matching original CZ file
+/// with each one once it has not changed for the debounce time
+#[cfg(target_os = "linux")]
+fn watch(
+    source: &Path,
+    output: &Path,
+    originals: &Path,
+    debounce: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    // Changed paths are reported as absolute paths
+    let source = &source.canonicalize()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(source, RecursiveMode::Recursive)?;
+
+    println!("Watching {} for changes", source.to_string_lossy());
+
+    // Editors often write a file several times when saving, so each image is
+    // only rebuilt once its last change is older than the debounce time
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        let timeout = pending.values().min().map_or(Duration::MAX, |t| {
+            (*t + debounce).saturating_duration_since(Instant::now())
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if ImageFormat::from_path(&path).is_ok_and(|f| f.reading_enabled()) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Ok(Err(e)) => pretty_error(&format!("Watch error: {}", e)),
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let now = Instant::now();
+        let ready: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, t)| now.duration_since(**t) >= debounce)
+            .map(|(p, _)| p.clone())
+            .collect();
+
+        for image in ready {
+            pending.remove(&image);
+            if !image.is_file() {
+                continue;
+            }
+
+            match rebuild_cz(&image, source, output, originals) {
+                Ok(cz_path) => println!(
+                    "Rebuilt {} from {}",
+                    cz_path.to_string_lossy(),
+                    image.to_string_lossy()
+                ),
+                Err(e) => pretty_error(&format!("{}: {}", image.to_string_lossy(), e)),
+            }
+        }
+    }
+}
+
+/// Replace the original CZ file matching an image in the source folder, and
+/// return the location of the new CZ file
+#[cfg(target_os = "linux")]
+fn rebuild_cz(
+    image: &Path,
+    source: &Path,
+    output: &Path,
+    originals: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let relative = image.strip_prefix(source)?;
+    let Some(original) = find_cz(&originals.join(relative)) else {
+        return Err("No original CZ file with the same name".into());
+    };
+
+    let final_output = output.join(original.strip_prefix(originals)?);
+    if let Some(parent) = final_output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    replace_cz(
+        &original,
+        &final_output,
+        &image.to_path_buf(),
+        &None,
+        &None,
+        true,
+        CropBoundReplacement {
+            auto_replace: true,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(final_output)
+}
+
+/// Find a CZ file with the same path as the given one, apart from the
+/// extension, which may also be missing
+#[cfg(target_os = "linux")]
+fn find_cz(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?;
+
+    let mut candidates: Vec<PathBuf> = fs::read_dir(path.parent()?)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.file_stem() == Some(stem) && p.is_file() && has_cz_magic(p))
+        .collect();
+
+    candidates.sort();
+    candidates.into_iter().next()
+}
+
+/// Save the color and alpha of a CZ file as two separate images
+fn split_cz(
+    input: &Path,
+    rgb_path: &Path,
+    alpha_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cz = cz::open(input)?;
+    let (width, height) = (cz.header().width() as u32, cz.header().height() as u32);
+    let (rgb, alpha) = split_alpha(cz.as_raw());
+
+    image::save_buffer(rgb_path, &rgb, width, height, ColorType::Rgb8)?;
+    image::save_buffer(alpha_path, &alpha, width, height, ColorType::L8)?;
+
+    Ok(())
+}
+
+/// Build a 32-bit CZ file from a color image and an alpha mask
+fn merge_cz(
+    rgb_path: &Path,
+    alpha_path: &Path,
+    output: &Path,
+    version: CzVersion,
+    original: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rgb = image::open(rgb_path)?.into_rgb8();
+    let alpha = image::open(alpha_path)?.into_luma8();
+
+    if rgb.dimensions() != alpha.dimensions() {
+        return Err(format!(
+            "The alpha mask is {}x{}, but the RGB image is {}x{}",
+            alpha.width(),
+            alpha.height(),
+            rgb.width(),
+            rgb.height()
+        )
+        .into());
+    }
+
+    let rgba = join_alpha(rgb.as_raw(), alpha.as_raw())?;
+    let mut cz = CzFile::from_raw(version, rgb.width() as u16, rgb.height() as u16, rgba);
+
+    if let Some(original) = original {
+        let original = CzInfo::open(original)?;
+        cz.header_mut()
+            .set_color_block(original.header().color_block());
+
+        if let Some(ext) = original.extended_header() {
+            cz = cz.with_extended_header(*ext);
+        }
+    }
+
+    cz.save_as_cz(output)?;
+
+    Ok(())
+}
+
+/// Write the palette of a CZ file to a palette file
+fn export_palette(input: &Path, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let cz = cz::open(input)?;
+    let Some(palette) = cz.palette() else {
+        return Err("Input is not an 8-bit indexed color image".into());
+    };
+
+    palette.save(output)?;
+
+    Ok(())
+}
+
+/// Re-encode an 8-bit CZ file with a new palette, keeping its indices
+fn apply_palette(
+    input: &Path,
+    output: &Path,
+    palette: &Palette,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cz = cz::open(input)?;
+    if cz.indices().is_none() {
+        return Err("Input is not an 8-bit indexed color image".into());
+    }
+
+    if let Some(max) = cz.indices().unwrap().iter().max()
+        && *max as usize >= palette.len()
+    {
+        return Err(format!(
+            "Palette has {} colors, but index {} is used",
+            palette.len(),
+            max
+        )
+        .into());
+    }
+
+    *cz.palette_mut() = Some(palette.clone());
+    cz.refresh_bitmap()?;
+
+    cz.save_as_cz(output)?;
+
+    Ok(())
+}
+
+#[derive(Default, Clone, Copy)]
+struct CropBoundReplacement {
+    pub auto_replace: bool,
+    pub crop: Option<(u16, u16)>,
+    pub bounds: Option<(u16, u16)>,
+    pub offset: Option<(u16, u16)>,
+}
+
+/// Replace a CZ file with the bitmap of a PNG file
+fn replace_cz<P: ?Sized + AsRef<Path>>(
+    input_path: &P,
+    output_path: &P,
+    replacement_path: &P,
+    version: &Option<u8>,
+    depth: &Option<u16>,
+    palette_clear: bool,
+    cb_info: CropBoundReplacement,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = input_path.as_ref();
+    if !path.is_file() {
+        return Err("Input path is not a file".into());
+    }
+
+    if !replacement_path.as_ref().exists() || !replacement_path.as_ref().is_file() {
+        return Err("Replacement path does not exist or is not a file".into());
     }
 
-    // Open the replacement image and convert it to RGBA8
-    let repl_img = image::open(replacement_path)?.to_rgba8();
+    // Open the replacement image
+    let repl_img = image::open(replacement_path)?;
 
     // Open the original CZ file
     let mut cz = cz::open(&path)?;
@@ -497,26 +2340,49 @@ fn replace_cz<P: ?Sized + AsRef<Path>>(
     });
 
     if !crop_equal {
-        println!("Crop will not be auto-modified for \"{}\"", path.to_string_lossy());
+        println!(
+            "Crop will not be auto-modified for \"{}\"",
+            path.to_string_lossy()
+        );
     }
 
     if !bounds_equal {
-        println!("Bounds will not be auto-modified for \"{}\"", path.to_string_lossy());
+        println!(
+            "Bounds will not be auto-modified for \"{}\"",
+            path.to_string_lossy()
+        );
     }
 
     // Set CZ header parameters and the new bitmap
     cz.header_mut().set_width(repl_img.width() as u16);
     cz.header_mut().set_height(repl_img.height() as u16);
-    cz.set_bitmap(repl_img.into_raw());
 
-    if palette_clear {
-        cz.clear_palette();
+    // Paletted replacements bring their own indices and palette, and
+    // grayscale images such as masks keep their palette when replaced with
+    // a grayscale image, so the values are written back without quantizing
+    let indexed = read_indexed_png(replacement_path)?.filter(|_| depth.is_none_or(|d| d == 8));
+    if let Some((indices, palette)) = indexed {
+        cz.header_mut().set_depth(8);
+        cz.set_indexed(indices, palette)?;
+    } else if cz.grayscale_ramp().is_some()
+        && repl_img.color() == ColorType::L8
+        && depth.is_none_or(|d| d == 8)
+    {
+        cz.set_luma8(&repl_img.into_luma8().into_raw())?;
+    } else {
+        cz.set_bitmap(repl_img.into_rgba8().into_raw());
+
+        if palette_clear {
+            cz.clear_palette();
+        }
     }
 
     // If the extended header exists and the width and height are the same
     // as the crop width and crop height, fix them to be the same.
     let header_ref = *cz.header();
-    if let Some(ext) = cz.extended_header_mut() && cb_info.auto_replace {
+    if let Some(ext) = cz.extended_header_mut()
+        && cb_info.auto_replace
+    {
         if crop_equal {
             ext.crop_width = header_ref.width();
             ext.crop_height = header_ref.height();
@@ -562,15 +2428,295 @@ fn replace_cz<P: ?Sized + AsRef<Path>>(
     }
 
     // Save the file to the proper output location
-    cz.save_as_cz(&output_path.as_ref()).unwrap();
+    cz.save_as_cz(&output_path.as_ref())?;
 
     Ok(())
 }
 
-fn parse_dimensions(dim: &Option<String>) -> Result<Option<(u16, u16)>, ParseIntError> {
-    let Some(dim) = dim else {
-        return Ok(None)
+/// Print the failures and a summary of a batch operation, and exit with an
+/// error if anything failed
+fn finish_batch(action: &str, files: &[PathBuf], failures: &[(usize, String)], skipped: usize) {
+    for (i, error) in failures {
+        pretty_error(&format!("{}: {}", files[*i].to_string_lossy(), error));
+    }
+
+    print!("{} {} files", action, files.len() - failures.len());
+    print!(", {} failed", failures.len());
+    if skipped > 0 {
+        print!(", {} skipped", skipped);
+    }
+    println!();
+
+    if !failures.is_empty() {
+        exit(1);
+    }
+}
+
+/// Save a decoded CZ file as an image, as a single channel image if it is an
+/// 8-bit grayscale image like a mask, or as a paletted PNG if requested
+fn export_image<P: ?Sized + AsRef<Path>>(
+    cz: &CzFile,
+    path: &P,
+    format: ImageFormat,
+    indexed: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if indexed && format != ImageFormat::Png {
+        return Err("Paletted output is only supported for PNG".into());
+    } else if indexed {
+        let (Some(indices), Some(palette)) = (cz.indices(), cz.palette()) else {
+            return Err("Image is not an 8-bit indexed color image".into());
+        };
+
+        return write_indexed_png(
+            path,
+            cz.header().width() as u32,
+            cz.header().height() as u32,
+            indices,
+            palette,
+        );
+    }
+
+    let width = cz.header().width() as u32;
+    let height = cz.header().height() as u32;
+
+    // Some formats cannot store single channel images, so those fall back
+    // to RGBA like everything else
+    if let Some(luma) = cz.to_luma8() {
+        match image::save_buffer_with_format(path, &luma, width, height, ColorType::L8, format) {
+            Err(ImageError::Unsupported(_)) => (),
+            result => return Ok(result?),
+        }
+    }
+
+    image::save_buffer_with_format(path, cz.as_raw(), width, height, ColorType::Rgba8, format)?;
+
+    Ok(())
+}
+
+/// Find an image which can be read with the same path as the given one,
+/// apart from the extension. PNG images are preferred.
+fn find_image(path: &Path) -> Option<PathBuf> {
+    let png = path.with_extension("png");
+    if png.is_file() {
+        return Some(png);
+    }
+
+    ImageFormat::all()
+        .filter(|f| f.reading_enabled())
+        .flat_map(|f| f.extensions_str())
+        .map(|e| path.with_extension(e))
+        .find(|p| p.is_file())
+}
+
+/// Parse an image format from its name or extension, which must be enabled
+/// in this build
+fn parse_image_format(name: &str) -> Result<ImageFormat, String> {
+    let Some(format) = ImageFormat::from_extension(name) else {
+        return Err(format!("\"{}\" is not a known image format", name));
+    };
+
+    if !format.writing_enabled() {
+        return Err(format!(
+            "Writing {:?} images is not enabled in this build of czutil",
+            format
+        ));
+    }
+
+    Ok(format)
+}
+
+fn parse_resize_filter(name: &str) -> Result<ResizeFilter, String> {
+    match name.to_lowercase().as_str() {
+        "nearest" => Ok(ResizeFilter::Nearest),
+        "bilinear" => Ok(ResizeFilter::Bilinear),
+        "bicubic" => Ok(ResizeFilter::Bicubic),
+        _ => Err(format!(
+            "\"{}\" is not a resize filter; must be nearest, bilinear or bicubic",
+            name
+        )),
+    }
+}
+
+/// Write palette indices and their palette as a paletted PNG, with the
+/// palette alpha values stored in the tRNS chunk
+fn write_indexed_png<P: ?Sized + AsRef<Path>>(
+    path: &P,
+    width: u32,
+    height: u32,
+    indices: &[u8],
+    palette: &Palette,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rgb: Vec<u8> = palette
+        .colors()
+        .iter()
+        .flat_map(|c| [c.r, c.g, c.b])
+        .collect();
+    let alpha: Vec<u8> = palette.colors().iter().map(|c| c.a).collect();
+
+    let out_file = BufWriter::new(fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(out_file, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(rgb);
+    encoder.set_trns(alpha);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(indices)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Palette indices along with the palette they refer to
+type IndexedBitmap = (Vec<u8>, Palette);
+
+/// Read the indices and palette of an 8-bit paletted PNG. Returns [`None`]
+/// if the file is not a PNG or is not paletted.
+fn read_indexed_png<P: ?Sized + AsRef<Path>>(
+    path: &P,
+) -> Result<Option<IndexedBitmap>, Box<dyn std::error::Error>> {
+    let mut decoder = png::Decoder::new(BufReader::new(fs::File::open(path)?));
+    decoder.set_transformations(png::Transformations::IDENTITY);
+
+    let Ok(mut reader) = decoder.read_info() else {
+        return Ok(None);
+    };
+
+    let info = reader.info();
+    if info.color_type != png::ColorType::Indexed || info.bit_depth != png::BitDepth::Eight {
+        return Ok(None);
+    }
+
+    let Some(rgb) = &info.palette else {
+        return Err("Paletted PNG is missing its palette".into());
     };
+    let alpha = info.trns.as_deref().unwrap_or_default();
+
+    let colors = rgb
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, c)| RGBA8::new(c[0], c[1], c[2], alpha.get(i).copied().unwrap_or(0xFF)))
+        .collect();
+
+    let mut indices = vec![0u8; reader.output_buffer_size().ok_or("PNG is too large")?];
+    let frame = reader.next_frame(&mut indices)?;
+    indices.truncate(frame.buffer_size());
+
+    Ok(Some((indices, Palette::new(colors))))
+}
+
+/// Parameters of a CZ file which are lost when decoding it to an image, kept
+/// in a JSON file next to the image so it can be encoded the same way again
+#[derive(Serialize, Deserialize)]
+struct Metadata {
+    version: u8,
+    depth: u16,
+    color_block: u8,
+    extended_header: Option<ExtendedMetadata>,
+
+    /// RGBA colors of the palette for 8-bit images
+    palette: Option<Vec<[u8; 4]>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExtendedMetadata {
+    offset_x: u16,
+    offset_y: u16,
+    crop_width: u16,
+    crop_height: u16,
+    bounds_width: u16,
+    bounds_height: u16,
+    unknown_width: Option<u16>,
+    unknown_height: Option<u16>,
+    unknown_1: u8,
+    unknown_2: Option<u32>,
+}
+
+impl Metadata {
+    fn from_cz(cz: &CzFile) -> Self {
+        let header = cz.header();
+
+        let extended_header = cz.extended_header().map(|ext| ExtendedMetadata {
+            offset_x: ext.offset_x,
+            offset_y: ext.offset_y,
+            crop_width: ext.crop_width,
+            crop_height: ext.crop_height,
+            bounds_width: ext.bounds_width,
+            bounds_height: ext.bounds_height,
+            unknown_width: ext.unknown_width,
+            unknown_height: ext.unknown_height,
+            unknown_1: ext.unknown_1(),
+            unknown_2: ext.unknown_2(),
+        });
+
+        let palette = cz
+            .palette()
+            .as_ref()
+            .map(|p| p.colors().iter().map(|c| [c.r, c.g, c.b, c.a]).collect());
+
+        Self {
+            version: header.version() as u8,
+            depth: header.depth(),
+            color_block: header.color_block(),
+            extended_header,
+            palette,
+        }
+    }
+
+    fn extended_header(&self) -> Option<ExtendedHeader> {
+        self.extended_header.as_ref().map(|ext| {
+            let mut header = ExtendedHeader::new()
+                .with_offset((ext.offset_x, ext.offset_y))
+                .with_crop((ext.crop_width, ext.crop_height))
+                .with_bounds((ext.bounds_width, ext.bounds_height));
+
+            header.unknown_width = ext.unknown_width;
+            header.unknown_height = ext.unknown_height;
+            header.set_unknown_1(ext.unknown_1);
+            header.set_unknown_2(ext.unknown_2);
+
+            header
+        })
+    }
+
+    fn palette(&self) -> Option<Palette> {
+        self.palette.as_ref().map(|p| {
+            Palette::new(
+                p.iter()
+                    .map(|c| RGBA8::new(c[0], c[1], c[2], c[3]))
+                    .collect(),
+            )
+        })
+    }
+
+    /// Load the metadata from a file, if it exists
+    fn load(path: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let file = BufReader::new(fs::File::open(path)?);
+        Ok(Some(serde_json::from_reader(file)?))
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let file = BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+
+        Ok(())
+    }
+}
+
+/// Get the location of the metadata file for an image, ex. `image.png.json`
+fn metadata_path(image_path: &Path) -> PathBuf {
+    let mut path = image_path.as_os_str().to_owned();
+    path.push(".json");
+
+    PathBuf::from(path)
+}
+
+fn parse_dimensions(dim: &Option<String>) -> Result<Option<(u16, u16)>, ParseIntError> {
+    let Some(dim) = dim else { return Ok(None) };
 
     let mut out = [0, 0];
     for (i, dimension) in dim.split('x').enumerate().take(2) {
@@ -583,3 +2729,7 @@ fn parse_dimensions(dim: &Option<String>) -> Result<Option<(u16, u16)>, ParseInt
 fn pretty_error(message: &str) {
     eprintln!("{}: {}", "Error".red().italic(), message);
 }
+
+fn pretty_warning(message: &str) {
+    eprintln!("{}: {}", "Warning".yellow().italic(), message);
+}
@@ -1,6 +1,6 @@
 use clap::{
-    error::{Error, ErrorKind},
     Parser, Subcommand,
+    error::{Error, ErrorKind},
 };
 use lbee_utils::{to_pretty_size, version};
 use luca_pak::Pak;
@@ -75,7 +75,7 @@ fn main() {
         exit(0);
     }
 
-    let mut pak = match Pak::open(&cli.input.unwrap()) {
+    let mut pak = match Pak::open_lazy(&cli.input.unwrap()) {
         Ok(pak) => pak,
         Err(err) => fmt_error(&format!("Could not open PAK file: {}", err)).exit(),
     };
@@ -102,7 +102,14 @@ fn main() {
                 } else {
                     outpath.push(entry.id().to_string());
                 }
-                entry.save(&outpath).unwrap();
+                if let Err(err) = entry.save(&outpath) {
+                    fmt_error(&format!(
+                        "Could not extract entry {}: {}",
+                        entry.index(),
+                        err
+                    ))
+                    .exit()
+                }
             }
         }
         Commands::Replace {
@@ -136,7 +143,9 @@ fn main() {
 
                     // Try replacing by name, if that fails, replace by parsed ID
                     if pak.replace_by_name(search_name, &rep_data).is_err() {
-                        if parsed_id.is_some() && pak.replace_by_id(parsed_id.unwrap(), &rep_data).is_err() {
+                        if parsed_id.is_some()
+                            && pak.replace_by_id(parsed_id.unwrap(), &rep_data).is_err()
+                        {
                             fmt_error("Could not replace entry in PAK: ID is invalid")
                                 .print()
                                 .unwrap()
@@ -177,11 +186,11 @@ fn main() {
                 } else if pak.replace_by_name(search_name, &rep_data).is_err() {
                     fmt_error("Could not replace entry in PAK: Could not find name").exit()
                 }
-
-                pak.save(&output).unwrap();
             }
 
-            pak.save(&output).unwrap();
+            if let Err(err) = pak.save(&output) {
+                fmt_error(&format!("Could not save PAK file: {}", err)).exit()
+            }
         }
         Commands::List => {
             print!("{}:", pak.path().to_string_lossy());
@@ -193,8 +202,14 @@ fn main() {
             println!("Entries:");
             for entry in pak.entries() {
                 print!("{:<2}", entry.index());
-                print!(" {:<12}:", entry.name().clone().unwrap_or("NO NAME".to_string()));
-                print!(" {} file", entry.file_type());
+                print!(
+                    " {:<12}:",
+                    entry.name().clone().unwrap_or("NO NAME".to_string())
+                );
+                match entry.file_type() {
+                    Ok(file_type) => print!(" {} file", file_type),
+                    Err(e) => print!(" unreadable file ({})", e),
+                }
                 print!(", {:>7}", to_pretty_size(entry.len() as u64));
                 print!(", id {}", entry.id());
                 println!();
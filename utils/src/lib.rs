@@ -1,4 +1,14 @@
 use owo_colors::OwoColorize;
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
 
 pub fn version(bin_name: &str) -> String {
     format!(
@@ -30,3 +40,63 @@ pub fn to_pretty_size(size: u64) -> String {
         size.to_string() + " B"
     }
 }
+
+/// Recursively list all files within a directory, sorted by path
+pub fn walk_files<P: ?Sized + AsRef<Path>>(dir: &P) -> Result<Vec<PathBuf>, io::Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.as_ref().to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// The default number of worker threads for batch operations
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Run a task on every item using a number of worker threads.
+///
+/// Returns the index and error message of every item which failed, in order.
+pub fn run_parallel<T, F>(items: &[T], jobs: usize, task: F) -> Vec<(usize, String)>
+where
+    T: Sync,
+    F: Fn(&T) -> Result<(), Box<dyn Error>> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+
+    thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            s.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+
+                    if let Err(e) = task(item) {
+                        failures.lock().unwrap().push((i, e.to_string()));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_by_key(|f| f.0);
+    failures
+}